@@ -1,7 +1,6 @@
 use lumenpyx::drawable_object::Drawable;
 use lumenpyx::primitives::{Normal, Sprite, Texture};
 use lumenpyx::{lights::LightDrawable, winit::event, *};
-use rand::Rng;
 
 fn main() {
     //let (event_loop, window, display, indices) = setup_program();
@@ -23,11 +22,27 @@ fn main() {
         )),
     ];
 
+    // each torch gets its own seed so they don't flicker in step
+    let animators: Vec<animation::LightAnimator> = lights
+        .iter()
+        .enumerate()
+        .map(|(i, light)| {
+            let mut animator = animation::LightAnimator::new(&**light, i as u64);
+            animator.add_modifier(animation::LightModifier::Flicker {
+                property: animation::LightProperty::Intensity,
+                amount: 0.5,
+                speed: 6.0,
+            });
+            animator
+        })
+        .collect();
+
     let scene_drawable = Sprite::new(
         "../images/Demo-Scene-Albedo.png".into(),
         "../images/Demo-Scene-Heightmap.png".into(),
         "../images/Demo-Scene-Roughnessmap.png".into(),
         Normal::AutoGenerated,
+        [0.0, 0.0, 0.0, 0.0].into(),
         &lumen_program,
         Transform::new([0.0, 0.0, 0.0]),
     );
@@ -36,7 +51,7 @@ fn main() {
     let mut start_of_60_frame = std::time::Instant::now();
     let camera = Camera::new([0.0, 0.0, 1.5]);
 
-    let mut t: f32 = 0.0;
+    let start = std::time::Instant::now();
     lumen_program.run(event_loop, |mut program| {
         distance_to_60_frame -= 1.0;
         if distance_to_60_frame < 0.0 {
@@ -45,13 +60,9 @@ fn main() {
             start_of_60_frame = std::time::Instant::now();
         }
 
-        {
-            for light in lights.iter_mut() {
-                let mut rng = rand::thread_rng();
-                t += rng.gen_range(0.0..0.01);
-                light.set_intensity(1.0 + (t.sin() * 0.5) as f32);
-                //light.set_position((t.sin() * 0.5) as f32, (t.cos() * 0.5) as f32, 1.0);
-            }
+        let time = start.elapsed().as_secs_f32();
+        for (light, animator) in lights.iter_mut().zip(animators.iter()) {
+            animator.animate(&mut **light, time);
         }
 
         let drawable_refs: Vec<&dyn Drawable> = vec![&scene_drawable];
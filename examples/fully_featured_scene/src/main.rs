@@ -37,6 +37,7 @@ fn main() {
         "../images/Demo_Town/Demo-town-Heightmap-Bottom.png".into(),
         [0.0, 0.0, 0.0, 1.0].into(),
         [0.0, 0.96, 0.48, 1.0].into(),
+        [0.0, 0.0, 0.0, 0.0].into(),
         &lumen_program,
         Transform::new([0.0, 0.0, 0.0]),
     );
@@ -47,6 +48,7 @@ fn main() {
         "../images/Demo_Town/Demo-town-Heightmap-Top.png".into(),
         "../images/Demo_Town/Demo-town-roughness.png".into(),
         [0.0, 0.96, 0.48, 1.0].into(),
+        [0.0, 0.0, 0.0, 0.0].into(),
         &lumen_program,
         Transform::new([0.0, 0.0, 0.0]),
     );
@@ -66,6 +68,7 @@ fn main() {
             0.34.into(),
             0.0.into(),
             Default::default(),
+            [0.0, 0.0, 0.0, 0.0].into(),
             &lumen_program,
             Transform::new([0.0, 0.063, 0.0]),
         ));
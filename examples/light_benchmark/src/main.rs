@@ -14,6 +14,7 @@ fn main() {
         "../images/Demo-Scene-Heightmap.png".into(),
         "../images/Demo-Scene-Roughnessmap.png".into(),
         Normal::AutoGenerated,
+        [0.0, 0.0, 0.0, 0.0].into(),
         &lumen_program,
         Transform::new([0.0, 0.0, 0.0]),
     );
@@ -24,10 +25,14 @@ fn main() {
     lumen_program.run(event_loop, |mut program| {
         distance_to_60_frame -= 1.0;
         if distance_to_60_frame < 0.0 {
+            let render_stats = program.get_render_stats();
             println!(
-                "FPS: {} with {} lights",
+                "FPS: {} with {} lights ({} batched in {} passes, {} drawn individually)",
                 (60.0 / start_of_60_frame.elapsed().as_secs_f32()).round(),
-                lights.len()
+                lights.len(),
+                render_stats.batched_point_lights,
+                render_stats.batched_light_passes,
+                render_stats.individual_lights
             );
             distance_to_60_frame = 60.0;
 
@@ -37,21 +42,22 @@ fn main() {
                 rand::thread_rng().gen_range(-1.0..1.0),
             ];
 
-            /*lights.push(Box::new(lights::PointLight::new(
+            // point lights are shaded together in batches, area lights each take their own pass
+            lights.push(Box::new(lights::PointLight::new(
                 random_position,
                 [1.0, 0.76, 0.52],
                 0.1,
                 0.02,
-            )));*/
+            )));
 
-            lights.push(Box::new(lights::AreaLight::new(
+            /*lights.push(Box::new(lights::AreaLight::new(
                 random_position,
                 [1.0, 0.76, 0.52],
                 0.1,
                 0.02,
                 0.1,
                 0.1,
-            )));
+            )));*/
 
             // this needs to be after the push so that the time is accurate
             start_of_60_frame = std::time::Instant::now();
@@ -29,6 +29,7 @@ fn main() {
             path.into(),
             [0.0, 0.0, 0.0, 0.0].into(),
             Normal::AutoGenerated,
+            [0.0, 0.0, 0.0, 0.0].into(),
             &lumen_program,
             Transform::new([0.0, 0.0, 0.0]),
         );
@@ -21,6 +21,7 @@ fn main() {
         "../images/Test Grid Color Heightmap.png".into(),
         "../images/Test Grid Color Roughnessmap.png".into(),
         Normal::AutoGenerated,
+        [0.0, 0.0, 0.0, 0.0].into(),
         &lumen_program,
         Transform::new([0.0, 0.0, 0.0]),
     );
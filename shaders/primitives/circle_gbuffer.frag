@@ -0,0 +1,24 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 albedo;
+out vec4 height;
+out vec4 material;
+out vec4 normal;
+
+#include "gbuffer_blend.glsl"
+
+uniform vec4 circle_color;
+uniform float radius_squared;
+
+void main() {
+    float x = v_tex_coords.x - 0.5;
+    float y = v_tex_coords.y - 0.5;
+    float distance_squared = x * x + y * y;
+    if (distance_squared >= radius_squared) {
+        discard;
+    }
+
+    // a flat circle on the ground
+    write_gbuffer(circle_color, vec3(0.0), vec3(0.0), vec3(0.0, 0.0, 1.0));
+}
@@ -0,0 +1,30 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 albedo;
+out vec4 height;
+out vec4 material;
+out vec4 normal;
+
+#include "gbuffer_blend.glsl"
+
+uniform float width;
+uniform float height_on_screen;
+uniform vec4 cylinder_color;
+uniform vec2 resolution;
+
+void main() {
+    float x = v_tex_coords.x;
+    float y = v_tex_coords.y;
+    // center the rectangle
+    if (x < 0.5 - width / 2 || x > 0.5 + width / 2 || y < 0.5 - height_on_screen / 2 || y > 0.5 + height_on_screen / 2) {
+        discard;
+    }
+
+    // a cylinder lying along the y axis
+    x = x - 0.5;
+    float z = sqrt((width / 2) * (width / 2) - x * x);
+    vec2 dir = -vec2(dFdx(z), dFdy(z)) * resolution;
+
+    write_gbuffer(cylinder_color, vec3(z), vec3(0.0), normalize(vec3(dir, 1.0)));
+}
@@ -0,0 +1,54 @@
+// blending for the gbuffer shaders, include it with #include "gbuffer_blend.glsl"
+// it expects the outputs `out vec4 albedo; out vec4 height; out vec4 material; out vec4 normal;` to be declared before it is included
+// every output is blended the same way, so what each output writes decides how it covers the channel beneath it
+// the modes match BlendMode in drawable_object.rs
+
+const int BLEND_NORMAL = 0;
+const int BLEND_ADDITIVE = 1;
+const int BLEND_MULTIPLY = 2;
+const int BLEND_SCREEN = 3;
+
+// below this alpha a pixel is too see through to have its own height, material and normal
+const float GBUFFER_OPAQUE_ALPHA = 0.5;
+
+uniform int blend_mode;
+// the modes other than normal draw the surface and the albedo in separate passes,
+// these say which of them this pass writes
+uniform bool write_albedo;
+uniform bool write_surface;
+
+// what an output has to be to leave the channel beneath it unchanged
+vec4 keep_channel() {
+    // only the pass that writes the albedo has the blending of the blend mode
+    if (write_albedo && blend_mode == BLEND_MULTIPLY) {
+        return vec4(1.0, 1.0, 1.0, 0.0);
+    }
+    return vec4(0.0);
+}
+
+// the albedo made ready for the blending of the blend mode
+vec4 blend_albedo(vec4 color) {
+    if (blend_mode == BLEND_MULTIPLY) {
+        return vec4(mix(vec3(1.0), color.rgb, color.a), color.a);
+    } else if (blend_mode == BLEND_ADDITIVE || blend_mode == BLEND_SCREEN) {
+        return vec4(color.rgb * color.a, color.a);
+    }
+    return color;
+}
+
+// write every channel, color is the albedo and its alpha is how much the pixel covers what is beneath it
+// translucent pixels keep the height, material and normal beneath them whatever the blend mode
+void write_gbuffer(vec4 color, vec3 surface_height, vec3 surface_material, vec3 surface_normal) {
+    vec4 keep = keep_channel();
+
+    albedo = write_albedo ? blend_albedo(color) : keep;
+    if (write_surface && color.a >= GBUFFER_OPAQUE_ALPHA) {
+        height = vec4(surface_height, 1.0);
+        material = vec4(surface_material, 1.0);
+        normal = vec4(surface_normal, 1.0);
+    } else {
+        height = keep;
+        material = keep;
+        normal = keep;
+    }
+}
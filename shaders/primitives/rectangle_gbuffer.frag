@@ -0,0 +1,25 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 albedo;
+out vec4 height;
+out vec4 material;
+out vec4 normal;
+
+#include "gbuffer_blend.glsl"
+
+uniform float width;
+uniform float height_on_screen;
+uniform vec4 rect_color;
+
+void main() {
+    float x = v_tex_coords.x;
+    float y = v_tex_coords.y;
+    // center the rectangle
+    if (x < 0.5 - width / 2 || x > 0.5 + width / 2 || y < 0.5 - height_on_screen / 2 || y > 0.5 + height_on_screen / 2) {
+        discard;
+    }
+
+    // a flat rectangle on the ground
+    write_gbuffer(rect_color, vec3(0.0), vec3(0.0), vec3(0.0, 0.0, 1.0));
+}
@@ -0,0 +1,30 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 albedo;
+out vec4 height;
+out vec4 material;
+out vec4 normal;
+
+#include "gbuffer_blend.glsl"
+
+uniform vec4 sphere_color;
+uniform float radius_squared;
+uniform vec2 resolution;
+
+void main() {
+    float x = v_tex_coords.x - 0.5;
+    float y = v_tex_coords.y - 0.5;
+    float distance_squared = x * x + y * y;
+    if (distance_squared >= radius_squared) {
+        discard;
+    }
+
+    // the z coordinate as seen in the heightmap
+    float z = sqrt(radius_squared - distance_squared);
+
+    // this will always be the same for any normal shader, given z is a function of height
+    vec2 dir = -vec2(dFdx(z), dFdy(z)) * resolution;
+
+    write_gbuffer(sphere_color, vec3(z), vec3(0.0), normalize(vec3(dir, 1.0)));
+}
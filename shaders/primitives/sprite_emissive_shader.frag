@@ -0,0 +1,17 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D image;
+// the albedo of the sprite, so it covers the emissive of whatever is beneath it
+uniform sampler2D coverage;
+uniform float intensity;
+
+void main() {
+    if (texture(coverage, v_tex_coords).a == 0.0) {
+        discard;
+    }
+    vec4 emissive = texture(image, v_tex_coords);
+    color = vec4(emissive.rgb * emissive.a * intensity, 1.0);
+}
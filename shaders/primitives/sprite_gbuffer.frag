@@ -0,0 +1,28 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 albedo;
+out vec4 height;
+out vec4 material;
+out vec4 normal;
+
+#include "gbuffer_blend.glsl"
+
+uniform sampler2D albedo_map;
+uniform sampler2D height_map;
+uniform sampler2D material_map;
+uniform sampler2D normal_map;
+
+void main() {
+    vec4 albedo_color = texture(albedo_map, v_tex_coords);
+    if (albedo_color.a == 0.0) {
+        discard;
+    }
+
+    write_gbuffer(
+        albedo_color,
+        texture(height_map, v_tex_coords).rgb,
+        texture(material_map, v_tex_coords).rgb,
+        texture(normal_map, v_tex_coords).rgb
+    );
+}
@@ -0,0 +1,21 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// r = how much of the shadow strength the pixel receives, g = how much light it receives
+uniform sampler2D mask;
+// the albedo of the sprite, so it covers the shadow strength of whatever is beneath it
+uniform sampler2D coverage;
+uniform float shadow_strength;
+// the light layers of the sprite as a bitmask divided by 255
+uniform float light_layers;
+
+void main() {
+    if (texture(coverage, v_tex_coords).a == 0.0) {
+        discard;
+    }
+    vec4 recieve = texture(mask, v_tex_coords);
+    float strength = shadow_strength * recieve.r;
+    color = vec4(strength, light_layers, recieve.g, strength);
+}
@@ -0,0 +1,18 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D albedomap;
+uniform sampler2D aomap;
+uniform vec3 ambient_light;
+
+void main() {
+    vec4 albedo = texture(albedomap, v_tex_coords);
+    if (albedo.a == 0.0) {
+        discard;
+    }
+
+    float visibility = texture(aomap, v_tex_coords).r;
+    color = vec4(albedo.rgb * ambient_light * visibility, 1.0);
+}
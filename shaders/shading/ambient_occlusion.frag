@@ -0,0 +1,51 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+// how far away pixels can occlude in pixels
+uniform float radius;
+uniform int samples;
+uniform float strength;
+
+const float PI = 3.14159265359;
+// spreads the samples out evenly over the disk
+const float GOLDEN_ANGLE = 2.39996322973;
+// how much higher a pixel has to be before it occludes
+const float HEIGHT_BIAS = 0.005;
+
+// a cheap per pixel random number, this rotates the samples so they don't band
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    if (texture(albedomap, v_tex_coords).a == 0.0) {
+        color = vec4(1.0);
+        return;
+    }
+
+    vec2 resolution = textureSize(heightmap, 0);
+    vec2 pixel = v_tex_coords * resolution;
+    float height = textureLod(heightmap, v_tex_coords, 0.0).r;
+    float rotation = hash(pixel) * 2.0 * PI;
+
+    float occlusion = 0.0;
+    float total_weight = 0.0;
+    for (int i = 0; i < samples; i++) {
+        float distance = radius * sqrt((float(i) + 0.5) / float(samples));
+        float angle = rotation + GOLDEN_ANGLE * float(i);
+        vec2 sample_coords = (pixel + vec2(cos(angle), sin(angle)) * distance) / resolution;
+
+        // closer pixels occlude more than far away ones
+        float weight = 1.0 - distance / (radius + 1.0);
+        float height_difference = textureLod(heightmap, sample_coords, 0.0).r - height - HEIGHT_BIAS;
+        occlusion += clamp(height_difference * radius / distance, 0.0, 1.0) * weight;
+        total_weight += weight;
+    }
+
+    float visibility = clamp(1.0 - strength * occlusion / total_weight, 0.0, 1.0);
+    color = vec4(vec3(visibility), 1.0);
+}
@@ -0,0 +1,25 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D image;
+// one texel in the direction of the blur
+uniform vec2 direction;
+uniform int radius;
+uniform float intensity;
+
+// a separable gaussian blur, this is run once horizontally and once vertically
+void main() {
+    float sigma = max(float(radius) / 2.0, 1.0);
+    vec3 total = vec3(0.0);
+    float total_weight = 0.0;
+
+    for (int i = -radius; i <= radius; i++) {
+        float weight = exp(-float(i * i) / (2.0 * sigma * sigma));
+        total += texture(image, v_tex_coords + direction * float(i)).rgb * weight;
+        total_weight += weight;
+    }
+
+    color = vec4(total / total_weight * intensity, 1.0);
+}
@@ -0,0 +1,73 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// the light leaving every pixel, this is the lit image for the first bounce
+// and the previous bounce for every bounce after that
+uniform sampler2D radiancemap;
+uniform sampler2D albedomap;
+uniform sampler2D heightmap;
+// the ambient occlusion, 1.0 is fully open
+uniform sampler2D aomap;
+uniform int rays;
+uniform int steps;
+// how far the rays go in pixels
+uniform float radius;
+uniform float intensity;
+
+const float PI = 3.14159265359;
+// how much higher a pixel has to be before it blocks the light
+const float HEIGHT_BIAS = 0.01;
+
+// a cheap per pixel random number, this rotates the rays so they don't band
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    vec4 albedo = texture(albedomap, v_tex_coords);
+    if (albedo.a == 0.0) {
+        discard;
+    }
+
+    vec2 resolution = textureSize(heightmap, 0);
+    vec2 pixel = v_tex_coords * resolution;
+    float receiver_height = textureLod(heightmap, v_tex_coords, 0.0).r;
+    float step_length = radius / float(steps);
+    float rotation = hash(pixel) * 2.0 * PI / float(rays);
+
+    vec3 gathered = vec3(0.0);
+    for (int i = 0; i < rays; i++) {
+        float angle = rotation + 2.0 * PI * float(i) / float(rays);
+        vec2 direction = vec2(cos(angle), sin(angle));
+
+        // the highest point between the receiver and the sample, anything lower than this can't be seen
+        float horizon = receiver_height;
+        vec3 ray_light = vec3(0.0);
+        for (int j = 1; j <= steps; j++) {
+            vec2 sample_pixel = pixel + direction * step_length * float(j);
+            vec2 sample_coords = sample_pixel / resolution;
+            if (sample_coords.x < 0.0 || sample_coords.x > 1.0 || sample_coords.y < 0.0 || sample_coords.y > 1.0) {
+                break;
+            }
+
+            float sample_height = textureLod(heightmap, sample_coords, 0.0).r;
+            if (sample_height + HEIGHT_BIAS >= horizon) {
+                float distance = step_length * float(j) / radius;
+                ray_light += texture(radiancemap, sample_coords).rgb / (1.0 + distance * distance * 4.0);
+            }
+
+            // anything taller than the receiver blocks the rest of the ray
+            horizon = max(horizon, sample_height);
+            if (horizon > receiver_height + HEIGHT_BIAS && sample_height < horizon) {
+                break;
+            }
+        }
+        gathered += ray_light / float(steps);
+    }
+
+    gathered /= float(rays);
+    gathered *= texture(aomap, v_tex_coords).r;
+    color = vec4(albedo.rgb * gathered * intensity, 1.0);
+}
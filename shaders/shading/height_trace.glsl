@@ -0,0 +1,93 @@
+// hierarchical ray marching over the height map, include it with #include "height_trace.glsl"
+// it expects a `uniform sampler2D heightmap;` to be declared before it is included
+// the mipmaps of the height map hold the max height of every block of pixels below them,
+// so whole blocks the ray passes over can be skipped at once
+// all the positions are in pixels, with the height in the z component
+
+// a safety net so a bad ray can't hang the gpu
+const int MAX_TRACE_STEPS = 1024;
+
+// march from p1 to p2 and find the first pixel that is higher than the ray
+// the pixels that p1 and p2 are in never count as hits
+// if stop_off_screen is true the ray misses as soon as it leaves the screen,
+// otherwise the parts of the ray that are off screen are skipped
+bool trace_heightmap(vec3 p1, vec3 p2, bool stop_off_screen, out ivec2 hit_pixel) {
+    hit_pixel = ivec2(0, 0);
+
+    ivec2 start_pixel = ivec2(floor(p1.xy));
+    ivec2 end_pixel = ivec2(floor(p2.xy));
+    if (start_pixel == end_pixel) {
+        return false;
+    }
+
+    ivec2 resolution = textureSize(heightmap, 0);
+    int max_level = int(floor(log2(float(max(resolution.x, resolution.y)))));
+
+    vec2 delta = p2.xy - p1.xy;
+    // nudges the ray just past the edge of a cell, a hundredth of a pixel
+    float t_epsilon = 0.01 / length(delta);
+
+    int level = 0;
+    float t = 0.0;
+    for (int i = 0; i < MAX_TRACE_STEPS && t < 1.0; i++) {
+        vec2 position = p1.xy + delta * t;
+        ivec2 pixel = ivec2(floor(position));
+
+        if (pixel.x < 0 || pixel.y < 0 || pixel.x >= resolution.x || pixel.y >= resolution.y) {
+            if (stop_off_screen) {
+                return false;
+            }
+        }
+
+        ivec2 cell = pixel >> level;
+        float cell_size = float(1 << level);
+
+        // find where the ray leaves this cell
+        vec2 cell_min = vec2(cell) * cell_size;
+        vec2 cell_max = cell_min + cell_size;
+        float t_exit = 1.0;
+        if (delta.x > 0.0) {
+            t_exit = min(t_exit, (cell_max.x - p1.x) / delta.x);
+        } else if (delta.x < 0.0) {
+            t_exit = min(t_exit, (cell_min.x - p1.x) / delta.x);
+        }
+        if (delta.y > 0.0) {
+            t_exit = min(t_exit, (cell_max.y - p1.y) / delta.y);
+        } else if (delta.y < 0.0) {
+            t_exit = min(t_exit, (cell_min.y - p1.y) / delta.y);
+        }
+
+        ivec2 level_resolution = textureSize(heightmap, level);
+        bool cell_on_screen = cell.x >= 0 && cell.y >= 0 && cell.x < level_resolution.x && cell.y < level_resolution.y;
+        bool pixel_on_screen = pixel.x >= 0 && pixel.y >= 0 && pixel.x < resolution.x && pixel.y < resolution.y;
+
+        if (!cell_on_screen) {
+            if (pixel_on_screen && level > 0) {
+                // the leftover edge of an odd sized level, look closer
+                level--;
+            } else {
+                // nothing off screen can block the ray
+                t = t_exit + t_epsilon;
+                level = min(level + 1, max_level);
+            }
+            continue;
+        }
+
+        // the ray is straight, so its lowest point in the cell is where it enters or leaves
+        float ray_height = min(mix(p1.z, p2.z, t), mix(p1.z, p2.z, t_exit));
+        float max_height = texelFetch(heightmap, cell, level).r;
+
+        if (max_height <= ray_height) {
+            t = t_exit + t_epsilon;
+            level = min(level + 1, max_level);
+        } else if (level > 0) {
+            level--;
+        } else if (pixel != start_pixel && pixel != end_pixel) {
+            hit_pixel = pixel;
+            return true;
+        } else {
+            t = t_exit + t_epsilon;
+        }
+    }
+    return false;
+}
@@ -0,0 +1,59 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// the light that reached every pixel, at a lower resolution than the rest
+uniform sampler2D lightmap;
+uniform sampler2D albedomap;
+uniform sampler2D heightmap;
+uniform sampler2D normalmap;
+
+// how quickly a difference in height stops the light from blending across it
+const float HEIGHT_SHARPNESS = 64.0;
+// how quickly a difference in normal stops the light from blending across it
+const float NORMAL_SHARPNESS = 8.0;
+
+void main() {
+    vec4 albedo = texture(albedomap, v_tex_coords);
+    if (albedo.a == 0.0) {
+        discard;
+    }
+
+    float height = textureLod(heightmap, v_tex_coords, 0.0).r;
+    vec3 normal = normalize(texture(normalmap, v_tex_coords).xyz);
+
+    vec2 low_res_size = textureSize(lightmap, 0);
+    vec2 low_res_position = v_tex_coords * low_res_size - 0.5;
+    vec2 base = floor(low_res_position);
+    vec2 bilinear = low_res_position - base;
+
+    // blend the four closest low res pixels, but only the ones that are on the same surface
+    vec4 light = vec4(0.0);
+    vec4 plain_light = vec4(0.0);
+    float total_weight = 0.0;
+    for (int x = 0; x < 2; x++) {
+        for (int y = 0; y < 2; y++) {
+            vec2 texel = clamp(base + vec2(x, y), vec2(0.0), low_res_size - 1.0);
+            vec2 texel_coords = (texel + 0.5) / low_res_size;
+            vec4 texel_light = texelFetch(lightmap, ivec2(texel), 0);
+
+            float bilinear_weight = (x == 0 ? 1.0 - bilinear.x : bilinear.x) * (y == 0 ? 1.0 - bilinear.y : bilinear.y);
+            float height_weight = exp(-abs(textureLod(heightmap, texel_coords, 0.0).r - height) * HEIGHT_SHARPNESS);
+            float normal_weight = pow(max(dot(normalize(texture(normalmap, texel_coords).xyz), normal), 0.0), NORMAL_SHARPNESS);
+            // empty pixels got no light at all, so they shouldn't darken their neighbours
+            float coverage_weight = texture(albedomap, texel_coords).a > 0.0 ? 1.0 : 0.001;
+
+            float weight = bilinear_weight * height_weight * normal_weight * coverage_weight;
+            light += texel_light * weight;
+            plain_light += texel_light * bilinear_weight;
+            total_weight += weight;
+        }
+    }
+
+    // if none of them are on the same surface, fall back to a plain bilinear blend
+    light = total_weight > 0.0001 ? light / total_weight : plain_light;
+
+    // the albedo is multiplied in at full resolution so the edges stay crisp
+    color = vec4(albedo.rgb * light.rgb, light.a);
+}
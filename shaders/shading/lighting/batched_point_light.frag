@@ -0,0 +1,64 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
+// one column per light
+// row 0 = position and intensity, row 1 = color and falloff,
+// row 2 = source radius, range, falloff model and light layer mask, row 3 = shadow color and opacity
+uniform sampler2D lightdata;
+uniform int light_count;
+
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+
+#include "shadows.glsl"
+#include "light_layers.glsl"
+#include "falloff.glsl"
+
+void main() {
+	vec4 albedo_color = texture(albedomap, v_tex_coords);
+	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
+	if (albedo_color.a == 0.0) {
+		discard;
+	}
+
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
+	int pixel_layers = pixel_light_layers(v_tex_coords);
+	vec3 new_v_tex_coords = vec3(textureSize(albedomap, 0) * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
+
+	// this is the same shading as point_light.frag, just added up for every light
+	color = vec4(0.0, 0.0, 0.0, 0.0);
+	for (int i = 0; i < light_count; i++) {
+		vec4 position_intensity = texelFetch(lightdata, ivec2(i, 0), 0);
+		vec4 color_falloff = texelFetch(lightdata, ivec2(i, 1), 0);
+		vec4 radius_range_model = texelFetch(lightdata, ivec2(i, 2), 0);
+		if ((pixel_layers & int(radius_range_model.a)) == 0) {
+			continue;
+		}
+		float light_source_radius = radius_range_model.r;
+
+		vec3 new_light_pos = vec3(textureSize(albedomap, 0) * position_intensity.xy, position_intensity.z);
+
+		float light_dist = distance(new_v_tex_coords, new_light_pos);
+		vec3 falloff = position_intensity.w * falloff_curve(light_dist, color_falloff.a, radius_range_model.g, int(radius_range_model.b));
+		vec4 shaded_color = albedo_color * apply_falloff(color_falloff.rgb, falloff);
+
+		// rgb = shadow color, a = shadow opacity, 0.0 when the light doesn't cast shadows
+		vec4 shadow_color_opacity = texelFetch(lightdata, ivec2(i, 3), 0);
+		float occlusion = 0.0;
+		if (shadow_color_opacity.a > 0.0) {
+			occlusion = shadow_occlusion(new_light_pos, new_v_tex_coords, light_source_radius, shadow_samples);
+		}
+		color += apply_shadow(shaded_color, occlusion * dimFactor * shadow_color_opacity.a, shadow_color_opacity.rgb);
+	}
+	color *= pixel_light_recieve(v_tex_coords);
+}
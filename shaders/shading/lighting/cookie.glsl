@@ -0,0 +1,29 @@
+// projected mask textures for the light shaders, include it with #include "cookie.glsl"
+// lights without a cookie get a plain white texture, so this never changes their light
+
+uniform sampler2D cookie_map;
+// in radians, counter clockwise
+uniform float cookie_rotation;
+uniform float cookie_scale;
+
+// the color of the cookie at coords, -1.0 to 1.0 covers the whole texture before it is scaled
+vec4 sample_cookie(vec2 coords) {
+    float s = sin(-cookie_rotation);
+    float c = cos(-cookie_rotation);
+    vec2 rotated = vec2(coords.x * c - coords.y * s, coords.x * s + coords.y * c);
+    vec4 cookie = texture(cookie_map, rotated / cookie_scale * 0.5 + 0.5);
+    // the alpha works as a brightness so plain masks can be drawn with transparency
+    return vec4(cookie.rgb * cookie.a, 1.0);
+}
+
+// project a point onto the plane facing away from the light,
+// so a cookie is spread across the cone of a light pointing in direction
+vec2 cone_cookie_coords(vec3 to_pixel, vec3 direction) {
+    vec3 forward = normalize(direction);
+    // any vector that isn't parallel to forward works to build the plane
+    vec3 helper = abs(forward.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(helper, forward));
+    vec3 up = cross(forward, right);
+    float along = max(dot(to_pixel, forward), 0.0001);
+    return vec2(dot(to_pixel, right), dot(to_pixel, up)) / along;
+}
@@ -6,6 +6,8 @@ out vec4 color;
 uniform sampler2D heightmap;
 uniform sampler2D albedomap;
 uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
 uniform vec3 light_pos;
 uniform vec3 light_color;
 uniform float light_intensity;
@@ -13,94 +15,18 @@ uniform float light_distance_falloff;
 uniform float light_angular_falloff;
 uniform vec3 light_direction;
 
-vec4 texture_pixel(sampler2D tex, vec2 coords) {
-    vec2 new_coords = coords / textureSize(tex, 0);
-	// if the coords are the v_tex_coords or the light_pos, return 0.0
-	if (coords == v_tex_coords || coords == light_pos.xy) {
-		return vec4(0.0, 0.0, 0.0, 0.0);
-	}
-    return texture(tex, new_coords);
-}
-
-/// Linearly interpolates between two points, P1 and P2 are the endpoints, and P3 is the point to interpolate to
-float lerp(vec3 P1, vec3 P2, vec2 P3) {
-	float t = clamp((dot(P3 - P1.xy, P2.xy - P1.xy) / dot(P2.xy - P1.xy, P2.xy - P1.xy)), 0.0, 1.0);
-	return mix(P1.z, P2.z, t);
-}
-
-// most this code attributed to https://gist.github.com/nowke/965fed0d5191bf373f1262be584207bb
-bool find_intersections(vec3 p1, vec3 p2) {
-    int x1 = int(round(p1.x));
-    int y1 = int(round(p1.y));
-    int x2 = int(round(p2.x));
-    int y2 = int(round(p2.y));
-
-	int dx, dy, i, e;
-	int incx, incy, inc1, inc2;
-	int x,y;
-
-	dx = x2-x1;
-	dy = y2-y1;
-
-	dx = abs(dx);
-	dy = abs(dy);
-	incx = 1;
-	if (x2 < x1) incx = -1;
-	incy = 1;
-	if (y2 < y1) incy = -1;
-	x = x1; y = y1;
-	if (dx > dy) {
-        // linear interpolation between the two points to get the height of the line at the current x and y
-        // TODO: double check the interpolation
-		float height_of_line = lerp(p1, p2, vec2(x, y));
-        if (texture_pixel(heightmap, vec2(x, y)).r > height_of_line) {
-            return true;
-        }
-
-		e = 2 * dy-dx;
-		inc1 = 2*(dy-dx);
-		inc2 = 2*dy;
-		for (i=0; i<dx; i++) {
-			if (e >= 0) {
-				y += incy;
-				e += inc1;
-			}
-			else
-			    e += inc2;
-			x += incx;
+uniform float light_source_radius;
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+// 0.0 when the light doesn't cast shadows, which skips tracing them
+uniform float shadow_opacity;
+uniform vec3 shadow_color;
+// bit i is set if the light shines on layer i
+uniform int light_layer_mask;
 
-			float height_of_line = lerp(p1, p2, vec2(x, y));
-            if (texture_pixel(heightmap, vec2(x, y)).r > height_of_line) {
-                return true;
-            }
-		}
-
-	} else {
-		float height_of_line = lerp(p1, p2, vec2(x, y));
-        if (texture_pixel(heightmap, vec2(x, y)).r > height_of_line) {
-            return true;
-        }
-
-		e = 2*dx-dy;
-		inc1 = 2*(dx-dy);
-		inc2 = 2*dx;
-		for (i=0; i<dy; i++) {
-			if (e >= 0) {
-				x += incx;
-				e += inc1;
-			}
-			else
-				e += inc2;
-			y += incy;
-
-			float height_of_line = lerp(p1, p2, vec2(x, y));
-            if (texture_pixel(heightmap, vec2(x, y)).r > height_of_line) {
-                return true;
-            }
-		}
-	}
-    return false;
-}
+#include "shadows.glsl"
+#include "light_layers.glsl"
+#include "cookie.glsl"
 
 // Function to calculate the angular distance between two vectors
 float angularDistance(vec3 P1, vec3 Origin, vec3 P2) {
@@ -118,12 +44,16 @@ float angularDistance(vec3 P1, vec3 Origin, vec3 P2) {
 void main() {
 	vec4 albedo_color = texture(albedomap, v_tex_coords);
 	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
-	if (albedo_color.a == 0.0) {
+	if (albedo_color.a == 0.0 || !in_light_layers(v_tex_coords, light_layer_mask)) {
 		discard;
 	}
 
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
     vec3 new_light_pos = vec3(textureSize(albedomap, 0) * (light_pos.xy), light_pos.z);
-    vec3 new_v_tex_coords = vec3(textureSize(albedomap, 0) * v_tex_coords, texture(heightmap, v_tex_coords).r);
+    vec3 new_v_tex_coords = vec3(textureSize(albedomap, 0) * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
     
 	float light_dist = distance(new_v_tex_coords, new_light_pos);
 	light_dist = max(light_dist * light_distance_falloff, 1.0);
@@ -131,12 +61,16 @@ void main() {
 	float light_angle = angularDistance(vec3(v_tex_coords, new_v_tex_coords.z), light_pos, light_direction);
 
     vec4 shaded_color = albedo_color * vec4(light_color, 1.0) * (light_intensity / (light_dist * light_dist * light_angle * light_angle));
-
-    if (!find_intersections(new_light_pos, new_v_tex_coords)) {
-		color = shaded_color;
-    }
-	else {
-		color = (shaded_color * dimFactor);
-    }
+	// the cookie is spread out from the light along its direction,
+	// the scale is how far it spreads sideways per pixel forward
+	vec3 to_pixel = vec3(new_v_tex_coords.xy - new_light_pos.xy, (new_v_tex_coords.z - new_light_pos.z) * textureSize(albedomap, 0).x);
+	shaded_color *= sample_cookie(cone_cookie_coords(to_pixel, light_direction));
+
+	float occlusion = 0.0;
+	if (shadow_opacity > 0.0) {
+		occlusion = shadow_occlusion(new_light_pos, new_v_tex_coords, light_source_radius, shadow_samples);
+	}
+	color = apply_shadow(shaded_color, occlusion * dimFactor * shadow_opacity, shadow_color);
+	color *= pixel_light_recieve(v_tex_coords);
 }
 
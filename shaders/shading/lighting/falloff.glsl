@@ -0,0 +1,48 @@
+// the falloff curves for the light shaders, include it with #include "falloff.glsl"
+// the models match FalloffModel in lights.rs
+
+const int FALLOFF_INVERSE_SQUARE = 0;
+const int FALLOFF_LINEAR = 1;
+const int FALLOFF_SMOOTHSTEP = 2;
+const int FALLOFF_CONSTANT = 3;
+const int FALLOFF_GRADIENT = 4;
+
+// the curve for FALLOFF_GRADIENT, read from left (at the light) to right (at the range)
+uniform sampler2D falloff_gradient;
+
+// how much of the light reaches a pixel distance pixels away
+// range is where the light reaches exactly zero, 0.0 means it has no range,
+// then the curves other than inverse square reach zero at 1.0 / falloff instead
+// this is a color so gradients can tint the light, the other curves are grey
+vec3 falloff_curve(float distance, float falloff, float range, int model) {
+    float t = range > 0.0 ? distance / range : distance * falloff;
+
+    if (model == FALLOFF_INVERSE_SQUARE) {
+        float scaled_distance = distance * falloff;
+        float inverse_square = 1.0 / (1.0 + scaled_distance * scaled_distance);
+        if (range > 0.0) {
+            // smoothly window the curve so it reaches zero at the range
+            float window = clamp(1.0 - t * t * t * t, 0.0, 1.0);
+            inverse_square *= window * window;
+        }
+        return vec3(inverse_square);
+    }
+
+    if (t >= 1.0) {
+        return vec3(0.0);
+    }
+
+    if (model == FALLOFF_LINEAR) {
+        return vec3(1.0 - t);
+    } else if (model == FALLOFF_SMOOTHSTEP) {
+        return vec3(1.0 - smoothstep(0.0, 1.0, t));
+    } else if (model == FALLOFF_GRADIENT) {
+        return texture(falloff_gradient, vec2(t, 0.5)).rgb;
+    }
+    return vec3(1.0);
+}
+
+// the light color scaled by the falloff, with the strongest channel in the alpha
+vec4 apply_falloff(vec3 light_color, vec3 falloff) {
+    return vec4(light_color * falloff, max(max(falloff.r, falloff.g), falloff.b));
+}
@@ -0,0 +1,19 @@
+// light layers for the light shaders, include it with #include "light_layers.glsl"
+// it expects a `uniform sampler2D shadow_strength_map;` to be declared before it is included
+// the layers of the drawable under each pixel are stored in the green channel of the shadow strength map
+// and how much light it receives in the blue channel
+
+// the layers of the drawable under the pixel, bit i is layer i
+int pixel_light_layers(vec2 coords) {
+    return int(texture(shadow_strength_map, coords).g * 255.0 + 0.5);
+}
+
+// does a light with this mask shine on the pixel
+bool in_light_layers(vec2 coords, int light_mask) {
+    return (pixel_light_layers(coords) & light_mask) != 0;
+}
+
+// how much of the light the pixel receives, 1.0 unless a drawable masks it
+float pixel_light_recieve(vec2 coords) {
+    return texture(shadow_strength_map, coords).b;
+}
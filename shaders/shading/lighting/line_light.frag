@@ -0,0 +1,76 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
+// the two ends of the line in 0.0 to 1.0 screen space, with the height in z
+uniform vec3 line_start;
+uniform vec3 line_end;
+// how thick the line is in pixels, it is full brightness inside of this
+uniform float line_radius;
+uniform vec3 light_color;
+uniform float light_intensity;
+uniform float light_falloff;
+// where the light reaches zero in pixels, 0.0 for no range
+uniform float light_range;
+uniform int falloff_model;
+
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+// 0.0 when the light doesn't cast shadows, which skips tracing them
+uniform float shadow_opacity;
+uniform vec3 shadow_color;
+// bit i is set if the light shines on layer i
+uniform int light_layer_mask;
+
+#include "shadows.glsl"
+#include "light_layers.glsl"
+#include "falloff.glsl"
+
+// the closest point to p on the segment from a to b
+vec3 closest_point_on_segment(vec3 p, vec3 a, vec3 b) {
+	vec2 ab = b.xy - a.xy;
+	float length_squared = dot(ab, ab);
+	if (length_squared == 0.0) {
+		return a;
+	}
+	float t = clamp(dot(p.xy - a.xy, ab) / length_squared, 0.0, 1.0);
+	return mix(a, b, t);
+}
+
+void main() {
+	vec4 albedo_color = texture(albedomap, v_tex_coords);
+	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
+	if (albedo_color.a == 0.0 || !in_light_layers(v_tex_coords, light_layer_mask)) {
+		discard;
+	}
+
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
+	vec2 resolution = textureSize(albedomap, 0);
+	vec3 new_line_start = vec3(resolution * line_start.xy, line_start.z);
+	vec3 new_line_end = vec3(resolution * line_end.xy, line_end.z);
+	vec3 new_v_tex_coords = vec3(resolution * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
+
+	// the light comes from the closest point on the line, like a point light that slides along it
+	vec3 closest_point = closest_point_on_segment(new_v_tex_coords, new_line_start, new_line_end);
+
+	float light_dist = max(distance(new_v_tex_coords, closest_point) - line_radius, 0.0);
+	vec3 falloff = light_intensity * falloff_curve(light_dist, light_falloff, light_range, falloff_model);
+	vec4 shaded_color = albedo_color * apply_falloff(light_color, falloff);
+
+	// the thickness of the line softens the shadows
+	float occlusion = 0.0;
+	if (shadow_opacity > 0.0) {
+		occlusion = shadow_occlusion(closest_point, new_v_tex_coords, line_radius, shadow_samples);
+	}
+	color = apply_shadow(shaded_color, occlusion * dimFactor * shadow_opacity, shadow_color);
+	color *= pixel_light_recieve(v_tex_coords);
+}
@@ -0,0 +1,60 @@
+#version 140
+
+// the shader of a ShadowedLight, the light's radiance function is added to the end of this file
+// it has to define
+// vec3 light_radiance(vec2 coords, vec3 pixel, vec3 light)
+// which returns how much light reaches the pixel without shadows,
+// pixel and light are in pixels with the height in the z component
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
+uniform vec3 light_pos;
+uniform vec3 light_color;
+uniform float light_intensity;
+// whatever the radiance function wants, set with ShadowedLight::set_params
+uniform vec4 light_params;
+
+uniform float light_source_radius;
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+// 0.0 when the light doesn't cast shadows, which skips tracing them
+uniform float shadow_opacity;
+uniform vec3 shadow_color;
+// bit i is set if the light shines on layer i
+uniform int light_layer_mask;
+
+#include "shadows.glsl"
+#include "light_layers.glsl"
+
+vec3 light_radiance(vec2 coords, vec3 pixel, vec3 light);
+
+void main() {
+	vec4 albedo_color = texture(albedomap, v_tex_coords);
+	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
+	if (albedo_color.a == 0.0 || !in_light_layers(v_tex_coords, light_layer_mask)) {
+		discard;
+	}
+
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
+	vec3 new_light_pos = vec3(textureSize(albedomap, 0) * (light_pos.xy), light_pos.z);
+	vec3 new_v_tex_coords = vec3(textureSize(albedomap, 0) * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
+
+	vec3 radiance = max(light_radiance(v_tex_coords, new_v_tex_coords, new_light_pos), vec3(0.0));
+	vec4 shaded_color = albedo_color * vec4(radiance, max(max(radiance.r, radiance.g), radiance.b));
+
+	float occlusion = 0.0;
+	if (shadow_opacity > 0.0) {
+		occlusion = shadow_occlusion(new_light_pos, new_v_tex_coords, light_source_radius, shadow_samples);
+	}
+	color = apply_shadow(shaded_color, occlusion * dimFactor * shadow_opacity, shadow_color);
+	color *= pixel_light_recieve(v_tex_coords);
+}
@@ -0,0 +1,68 @@
+// shared shadow tracing for the light shaders, include it with #include "shadows.glsl"
+// it expects a `uniform sampler2D heightmap;` to be declared before it is included
+// all the positions are in pixels, with the height in the z component
+
+// spreads the samples out evenly over the disk
+const float SHADOW_GOLDEN_ANGLE = 2.39996322973;
+
+#include "height_trace.glsl"
+
+// is there anything between the light and the pixel
+bool find_intersections(vec3 p1, vec3 p2) {
+    ivec2 hit_pixel;
+    return trace_heightmap(p1, p2, false, hit_pixel);
+}
+
+// darkens a lit color by how shadowed it is, 0.0 is fully lit and 1.0 is fully shadowed
+// fully shadowed pixels are multiplied by the shadow color, so black removes the light and other colors tint it
+vec4 apply_shadow(vec4 shaded_color, float shadow, vec3 shadow_color) {
+    vec3 tint = mix(vec3(1.0), shadow_color, clamp(shadow, 0.0, 1.0));
+    return shaded_color * vec4(tint, max(tint.r, max(tint.g, tint.b)));
+}
+
+// a cheap per pixel random number, this rotates the samples so they don't band
+float shadow_hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// how much of a round light is hidden from the pixel, 0.0 is fully lit and 1.0 is fully shadowed
+// the light is sampled over a disk of source_radius pixels, so bigger lights have softer shadows
+float shadow_occlusion(vec3 light, vec3 pixel, float source_radius, int samples) {
+    if (samples <= 1 || source_radius <= 0.0) {
+        return find_intersections(light, pixel) ? 1.0 : 0.0;
+    }
+
+    float rotation = shadow_hash(pixel.xy) * 6.28318530718;
+    float occluded = 0.0;
+    for (int i = 0; i < samples; i++) {
+        float distance = source_radius * sqrt((float(i) + 0.5) / float(samples));
+        float angle = rotation + SHADOW_GOLDEN_ANGLE * float(i);
+        vec3 sample_light = light + vec3(cos(angle), sin(angle), 0.0) * distance;
+        if (find_intersections(sample_light, pixel)) {
+            occluded += 1.0;
+        }
+    }
+    return occluded / float(samples);
+}
+
+// how much of a rectangular light is hidden from the pixel, 0.0 is fully lit and 1.0 is fully shadowed
+// the light is sampled over a jittered grid covering bmin to bmax,
+// closest is the point used when there is only one sample
+float shadow_occlusion_box(vec2 bmin, vec2 bmax, float light_height, vec3 closest, vec3 pixel, int samples) {
+    if (samples <= 1) {
+        return find_intersections(closest, pixel) ? 1.0 : 0.0;
+    }
+
+    int grid = int(ceil(sqrt(float(samples))));
+    float jitter = shadow_hash(pixel.xy);
+    float occluded = 0.0;
+    for (int i = 0; i < samples; i++) {
+        vec2 cell = vec2(mod(float(i), float(grid)), floor(float(i) / float(grid)));
+        vec2 t = (cell + fract(jitter + cell.yx * 0.618034)) / float(grid);
+        vec3 sample_light = vec3(mix(bmin, bmax, t), light_height);
+        if (find_intersections(sample_light, pixel)) {
+            occluded += 1.0;
+        }
+    }
+    return occluded / float(samples);
+}
@@ -0,0 +1,76 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
+uniform vec3 light_pos;
+// the direction the light points in, x and y along the screen and z straight up
+uniform vec3 light_direction;
+uniform vec3 light_color;
+uniform float light_intensity;
+// the cosines of the inner and outer cone angles
+uniform float cos_inner_angle;
+uniform float cos_outer_angle;
+// how far the light reaches in pixels
+uniform float light_range;
+uniform float light_source_radius;
+
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+// 0.0 when the light doesn't cast shadows, which skips tracing them
+uniform float shadow_opacity;
+uniform vec3 shadow_color;
+// bit i is set if the light shines on layer i
+uniform int light_layer_mask;
+
+#include "shadows.glsl"
+#include "light_layers.glsl"
+#include "cookie.glsl"
+
+void main() {
+	vec4 albedo_color = texture(albedomap, v_tex_coords);
+	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
+	if (albedo_color.a == 0.0 || !in_light_layers(v_tex_coords, light_layer_mask)) {
+		discard;
+	}
+
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
+	vec2 resolution = textureSize(albedomap, 0);
+	vec3 new_light_pos = vec3(resolution * light_pos.xy, light_pos.z);
+	vec3 new_v_tex_coords = vec3(resolution * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
+
+	// a height of 1.0 is as tall as the screen is wide, so the cone isn't squashed flat
+	vec3 to_pixel = vec3(new_v_tex_coords.xy - new_light_pos.xy, (new_v_tex_coords.z - new_light_pos.z) * resolution.x);
+	float light_dist = length(to_pixel);
+
+	// fades to exactly zero at the range instead of trailing off forever
+	float range_falloff = clamp(1.0 - (light_dist * light_dist) / (light_range * light_range), 0.0, 1.0);
+	range_falloff *= range_falloff;
+
+	float cos_angle = dot(to_pixel / max(light_dist, 0.0001), normalize(light_direction));
+	float cone_falloff = smoothstep(cos_outer_angle, cos_inner_angle, cos_angle);
+
+	vec4 shaded_color = albedo_color * vec4(light_color, 1.0) * light_intensity * range_falloff * cone_falloff;
+	// the cookie is spread across the cone, a scale of 1.0 fits it to the outer cone
+	float tan_outer_angle = sqrt(max(1.0 - cos_outer_angle * cos_outer_angle, 0.0)) / max(cos_outer_angle, 0.0001);
+	shaded_color *= sample_cookie(cone_cookie_coords(to_pixel, light_direction) / tan_outer_angle);
+	if (shaded_color.a == 0.0) {
+		color = vec4(0.0);
+		return;
+	}
+
+	float occlusion = 0.0;
+	if (shadow_opacity > 0.0) {
+		occlusion = shadow_occlusion(new_light_pos, new_v_tex_coords, light_source_radius, shadow_samples);
+	}
+	color = apply_shadow(shaded_color, occlusion * dimFactor * shadow_opacity, shadow_color);
+	color *= pixel_light_recieve(v_tex_coords);
+}
@@ -0,0 +1,75 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D heightmap;
+uniform sampler2D albedomap;
+uniform sampler2D shadow_strength_map;
+// r = reflectivity, g = roughness, b = metalness
+uniform sampler2D materialmap;
+// the direction the light comes from, x and y along the screen and z straight up
+uniform vec3 light_direction;
+uniform vec3 light_color;
+uniform float light_intensity;
+// how big the sun looks in radians, bigger suns cast softer shadows
+uniform float light_angular_radius;
+
+// how many traces are averaged for soft shadows
+uniform int shadow_samples;
+// 0.0 when the light doesn't cast shadows, which skips tracing them
+uniform float shadow_opacity;
+uniform vec3 shadow_color;
+// bit i is set if the light shines on layer i
+uniform int light_layer_mask;
+
+#include "shadows.glsl"
+#include "light_layers.glsl"
+
+void main() {
+	vec4 albedo_color = texture(albedomap, v_tex_coords);
+	float dimFactor = texture(shadow_strength_map, v_tex_coords).r;
+	if (albedo_color.a == 0.0 || !in_light_layers(v_tex_coords, light_layer_mask)) {
+		discard;
+	}
+
+	// metals show their color through reflections rather than diffuse light
+	vec4 material = texture(materialmap, v_tex_coords);
+	albedo_color.rgb *= 1.0 - material.r * material.b;
+
+	vec2 resolution = textureSize(albedomap, 0);
+	vec3 new_v_tex_coords = vec3(resolution * v_tex_coords, textureLod(heightmap, v_tex_coords, 0.0).r);
+
+	// the sun is infinitely far away, so it is just as bright everywhere
+	vec4 shaded_color = albedo_color * vec4(light_color, 1.0) * light_intensity;
+
+	float occlusion = 0.0;
+	float horizontal_length = length(light_direction.xy);
+	if (shadow_opacity > 0.0 && horizontal_length > 0.0001) {
+		vec2 direction = light_direction.xy / horizontal_length;
+
+		// follow the ray back to just past the edge of the screen, nothing beyond that can cast a shadow
+		float distance_to_edge = 1e20;
+		if (direction.x > 0.0) {
+			distance_to_edge = min(distance_to_edge, (resolution.x - new_v_tex_coords.x) / direction.x);
+		} else if (direction.x < 0.0) {
+			distance_to_edge = min(distance_to_edge, new_v_tex_coords.x / -direction.x);
+		}
+		if (direction.y > 0.0) {
+			distance_to_edge = min(distance_to_edge, (resolution.y - new_v_tex_coords.y) / direction.y);
+		} else if (direction.y < 0.0) {
+			distance_to_edge = min(distance_to_edge, new_v_tex_coords.y / -direction.y);
+		}
+		distance_to_edge += 1.0;
+
+		// a height of 1.0 is as tall as the screen is wide
+		float slope = light_direction.z / horizontal_length / resolution.x;
+		vec3 sun_pos = vec3(new_v_tex_coords.xy + direction * distance_to_edge, new_v_tex_coords.z + slope * distance_to_edge);
+
+		float source_radius = distance_to_edge * tan(light_angular_radius);
+		occlusion = shadow_occlusion(sun_pos, new_v_tex_coords, source_radius, shadow_samples);
+	}
+
+	color = apply_shadow(shaded_color, occlusion * dimFactor * shadow_opacity, shadow_color);
+	color *= pixel_light_recieve(v_tex_coords);
+}
@@ -0,0 +1,10 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D image;
+
+void main() {
+    color = texture(image, v_tex_coords);
+}
@@ -4,22 +4,28 @@ in vec2 v_tex_coords;
 out vec4 color;
 
 uniform sampler2D high_res_image;
+// the mip level of the high res image to read from
+uniform int high_res_level;
 // the resolution of the low res image an int
 uniform uvec2 new_resolution;
 
 void main() {
     // collect all colors that are in the pixel of the low res image in the high res image
-    vec4 new_color = vec4(0.0, 0.0, 0.0, 0.0);
-    vec2 high_res_resolution = textureSize(high_res_image, 0);
-
-    vec2 pixel_size = high_res_resolution / new_resolution;
+    ivec2 high_res_resolution = textureSize(high_res_image, high_res_level);
+    ivec2 low_res_resolution = ivec2(new_resolution);
+    ivec2 low_res_pixel = ivec2(gl_FragCoord.xy);
 
-    for (int i = 0; i < pixel_size.x; i++) {
-        for (int j = 0; j < pixel_size.y; j++) {
-            vec2 new_coords = v_tex_coords + (vec2(i, j) / high_res_resolution);
+    // the last pixel takes the leftover row or column when the size is odd
+    ivec2 start = low_res_pixel * high_res_resolution / low_res_resolution;
+    ivec2 end = ((low_res_pixel + 1) * high_res_resolution + low_res_resolution - 1) / low_res_resolution;
+    end = min(end, high_res_resolution);
 
-            if(texture(high_res_image, new_coords).r > new_color.r) {
-                new_color = texture(high_res_image, new_coords);
+    vec4 new_color = vec4(0.0, 0.0, 0.0, 0.0);
+    for (int x = start.x; x < end.x; x++) {
+        for (int y = start.y; y < end.y; y++) {
+            vec4 high_res_color = texelFetch(high_res_image, ivec2(x, y), high_res_level);
+            if (high_res_color.r > new_color.r) {
+                new_color = high_res_color;
             }
         }
     }
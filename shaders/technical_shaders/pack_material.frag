@@ -0,0 +1,26 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// the albedo of the sprite, only used for its alpha
+uniform sampler2D coverage;
+uniform sampler2D reflectivity_map;
+uniform sampler2D roughness_map;
+uniform sampler2D metalness_map;
+
+// packs the three material channels into one texture
+// r = reflectivity, g = roughness, b = metalness
+void main() {
+    float alpha = texture(coverage, v_tex_coords).a;
+    if (alpha <= 0.0) {
+        color = vec4(0.0, 0.0, 0.0, 0.0);
+        return;
+    }
+
+    float reflectivity = texture(reflectivity_map, v_tex_coords).r;
+    float roughness = texture(roughness_map, v_tex_coords).r;
+    float metalness = texture(metalness_map, v_tex_coords).r;
+
+    color = vec4(reflectivity, roughness, metalness, alpha);
+}
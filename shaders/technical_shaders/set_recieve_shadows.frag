@@ -6,6 +6,8 @@ out vec4 color;
 uniform sampler2D last_draw;
 uniform sampler2D this_draw;
 uniform float shadow_strength;
+// the light layers of the drawable as a bitmask divided by 255
+uniform float light_layers;
 
 
 void main() {
@@ -14,6 +16,7 @@ void main() {
     if (total_difference < 0.01) {
         discard;
     } else {
-        color = vec4(shadow_strength, shadow_strength, shadow_strength, shadow_strength);
+        // r and a = shadow strength, g = light layers, b = how much light the pixel receives
+        color = vec4(shadow_strength, light_layers, 1.0, shadow_strength);
     }
 }
\ No newline at end of file
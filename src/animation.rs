@@ -0,0 +1,422 @@
+/// The parts of a light that can be animated
+/// lights without a position or a radius leave them as None and ignore them when set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightState {
+    pub position: Option<[f32; 3]>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// where the light reaches zero in pixels, None for lights without a set range
+    pub radius: Option<f32>,
+}
+
+/// A light that a LightAnimator can drive, this is implemented for all the built-in lights
+/// implement it for a custom light to animate it in the same way
+pub trait AnimatableLight {
+    /// Get the current state of the light
+    fn get_light_state(&self) -> LightState;
+
+    /// Set the state of the light, anything the light doesn't have is ignored
+    fn set_light_state(&mut self, state: &LightState);
+}
+
+/// Which number a scalar modifier changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightProperty {
+    Intensity,
+    /// only does anything if the light has a range set
+    Radius,
+}
+
+/// A time driven change to a light
+/// every modifier is deterministic, the same seed and time always give the same result
+#[derive(Clone, Debug)]
+pub enum LightModifier {
+    /// random flicker like a torch or a candle,
+    /// amount is how far it dims from 0.0 - 1.0 and speed is how many times a second it changes
+    Flicker {
+        property: LightProperty,
+        amount: f32,
+        speed: f32,
+    },
+    /// smooth sine wave between min and max times the base value,
+    /// frequency is in cycles per second and phase is in cycles
+    Pulse {
+        property: LightProperty,
+        min: f32,
+        max: f32,
+        frequency: f32,
+        phase: f32,
+    },
+    /// hard on off blinking like an alarm,
+    /// duty_cycle is the part of each cycle the light is on from 0.0 - 1.0
+    /// and off is what the base value is multiplied by while it is off
+    Strobe {
+        property: LightProperty,
+        frequency: f32,
+        duty_cycle: f32,
+        off: f32,
+    },
+    /// smoothly blends through the colors and loops back to the first,
+    /// period is how many seconds a full loop takes, the colors are multiplied with the base color
+    ColorCycle { colors: Vec<[f32; 3]>, period: f32 },
+    /// random wandering around the base position like a swinging lantern,
+    /// amount is how far it can go on each axis and speed is how many times a second it changes
+    PositionJitter { amount: [f32; 3], speed: f32 },
+}
+
+impl LightModifier {
+    fn apply(&self, state: &mut LightState, time: f32, seed: u64) {
+        match self {
+            LightModifier::Flicker {
+                property,
+                amount,
+                speed,
+            } => {
+                let noise = value_noise(seed, 0, time * speed);
+                scale_property(state, *property, 1.0 - amount * noise);
+            }
+            LightModifier::Pulse {
+                property,
+                min,
+                max,
+                frequency,
+                phase,
+            } => {
+                let wave = ((time * frequency + phase) * std::f32::consts::TAU).sin() * 0.5 + 0.5;
+                scale_property(state, *property, min + (max - min) * wave);
+            }
+            LightModifier::Strobe {
+                property,
+                frequency,
+                duty_cycle,
+                off,
+            } => {
+                let on = (time * frequency).rem_euclid(1.0) < *duty_cycle;
+                if !on {
+                    scale_property(state, *property, *off);
+                }
+            }
+            LightModifier::ColorCycle { colors, period } => {
+                if colors.is_empty() || *period <= 0.0 {
+                    return;
+                }
+
+                let position = (time / period).rem_euclid(1.0) * colors.len() as f32;
+                let index = position.floor() as usize % colors.len();
+                let next = (index + 1) % colors.len();
+                let blend = position.fract();
+
+                for ((channel, from), to) in
+                    state.color.iter_mut().zip(colors[index]).zip(colors[next])
+                {
+                    *channel *= from + (to - from) * blend;
+                }
+            }
+            LightModifier::PositionJitter { amount, speed } => {
+                if let Some(position) = &mut state.position {
+                    for (i, (axis, amount)) in position.iter_mut().zip(amount).enumerate() {
+                        // each axis gets its own noise so the light doesn't only move diagonally
+                        let noise = value_noise(seed, i as u64 + 1, time * speed) * 2.0 - 1.0;
+                        *axis += amount * noise;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn scale_property(state: &mut LightState, property: LightProperty, scale: f32) {
+    match property {
+        LightProperty::Intensity => state.intensity *= scale,
+        LightProperty::Radius => {
+            if let Some(radius) = &mut state.radius {
+                *radius *= scale;
+            }
+        }
+    }
+}
+
+/// Drives a light from a base state and a list of modifiers
+/// the base state is what the light looks like with no modifiers,
+/// every frame call animate with the time in seconds to set the light
+/// ```ignore
+/// let mut torch = PointLight::new([0.5, 0.0, 1.0], [1.0, 0.76, 0.52], 2.0, 0.02);
+/// let mut animator = LightAnimator::new(&torch, 7);
+/// animator.add_modifier(LightModifier::Flicker {
+///     property: LightProperty::Intensity,
+///     amount: 0.4,
+///     speed: 8.0,
+/// });
+///
+/// // every frame
+/// animator.animate(&mut torch, time);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LightAnimator {
+    base: LightState,
+    modifiers: Vec<LightModifier>,
+    seed: u64,
+}
+
+impl LightAnimator {
+    /// Create a new animator using the current state of the light as the base
+    /// lights with different seeds flicker and jitter differently
+    pub fn new(light: &dyn AnimatableLight, seed: u64) -> LightAnimator {
+        LightAnimator {
+            base: light.get_light_state(),
+            modifiers: Vec::new(),
+            seed,
+        }
+    }
+
+    /// Add a modifier, modifiers are applied in the order they are added
+    pub fn add_modifier(&mut self, modifier: LightModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    /// Remove all the modifiers
+    pub fn clear_modifiers(&mut self) {
+        self.modifiers.clear();
+    }
+
+    /// Set the state the modifiers are applied on top of
+    pub fn set_base(&mut self, base: LightState) {
+        self.base = base;
+    }
+
+    /// Get the state the modifiers are applied on top of
+    pub fn get_base(&self) -> LightState {
+        self.base
+    }
+
+    /// Set the seed for the random modifiers
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Get the state of the light at a time in seconds without changing a light
+    pub fn sample(&self, time: f32) -> LightState {
+        let mut state = self.base;
+        for (i, modifier) in self.modifiers.iter().enumerate() {
+            // each modifier gets its own seed so two flickers on one light don't line up
+            modifier.apply(&mut state, time, hash(self.seed, i as u64));
+        }
+
+        state
+    }
+
+    /// Set the light to its state at a time in seconds
+    pub fn animate(&self, light: &mut dyn AnimatableLight, time: f32) {
+        light.set_light_state(&self.sample(time));
+    }
+}
+
+// splitmix64, cheap and good enough to not see patterns in a flicker
+fn hash(seed: u64, value: u64) -> u64 {
+    let mut x = seed ^ value.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// smooth noise from 0.0 - 1.0 that changes about once per unit of x
+fn value_noise(seed: u64, channel: u64, x: f32) -> f32 {
+    let seed = hash(seed, channel);
+    let cell = x.floor();
+    let blend = x - cell;
+    let blend = blend * blend * (3.0 - 2.0 * blend);
+
+    let a = hash(seed, cell as i64 as u64) as f32 / u64::MAX as f32;
+    let b = hash(seed, (cell as i64 + 1) as u64) as f32 / u64::MAX as f32;
+
+    a + (b - a) * blend
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestLight {
+        state: LightState,
+    }
+
+    impl AnimatableLight for TestLight {
+        fn get_light_state(&self) -> LightState {
+            self.state
+        }
+
+        fn set_light_state(&mut self, state: &LightState) {
+            self.state = *state;
+        }
+    }
+
+    fn test_light(position: Option<[f32; 3]>) -> TestLight {
+        TestLight {
+            state: LightState {
+                position,
+                color: [1.0, 1.0, 1.0],
+                intensity: 1.0,
+                radius: Some(100.0),
+            },
+        }
+    }
+
+    fn animator(modifier: LightModifier, seed: u64) -> LightAnimator {
+        let mut animator = LightAnimator::new(&test_light(Some([0.0, 0.0, 1.0])), seed);
+        animator.add_modifier(modifier);
+        animator
+    }
+
+    fn flicker() -> LightModifier {
+        LightModifier::Flicker {
+            property: LightProperty::Intensity,
+            amount: 0.5,
+            speed: 10.0,
+        }
+    }
+
+    fn strobe(duty_cycle: f32) -> LightModifier {
+        LightModifier::Strobe {
+            property: LightProperty::Intensity,
+            frequency: 1.0,
+            duty_cycle,
+            off: 0.0,
+        }
+    }
+
+    fn color_cycle(colors: Vec<[f32; 3]>) -> LightModifier {
+        LightModifier::ColorCycle {
+            colors,
+            period: 2.0,
+        }
+    }
+
+    fn assert_color_near(color: [f32; 3], expected: [f32; 3]) {
+        for (channel, expected) in color.iter().zip(expected) {
+            assert!(
+                (channel - expected).abs() < 1e-5,
+                "{color:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_seed_and_time_give_the_same_state() {
+        let first = animator(flicker(), 7);
+        let second = animator(flicker(), 7);
+
+        for frame in 0..100 {
+            let time = frame as f32 * 0.016;
+            assert_eq!(first.sample(time), second.sample(time));
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_states() {
+        let first = animator(flicker(), 7);
+        let second = animator(flicker(), 8);
+
+        let differs = (0..100).any(|frame| {
+            let time = frame as f32 * 0.016;
+            first.sample(time) != second.sample(time)
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn animate_sets_the_sampled_state() {
+        let animator = animator(flicker(), 3);
+        let mut light = test_light(Some([0.0, 0.0, 1.0]));
+
+        animator.animate(&mut light, 1.25);
+
+        assert_eq!(light.state, animator.sample(1.25));
+    }
+
+    #[test]
+    fn value_noise_stays_between_zero_and_one() {
+        for seed in 0..4 {
+            for step in -1000..1000 {
+                let noise = value_noise(seed, 0, step as f32 * 0.037);
+                assert!((0.0..=1.0).contains(&noise), "{noise} is out of range");
+            }
+        }
+    }
+
+    #[test]
+    fn strobe_is_on_for_the_duty_cycle() {
+        let animator = animator(strobe(0.25), 0);
+
+        assert_eq!(animator.sample(0.0).intensity, 1.0);
+        assert_eq!(animator.sample(0.2).intensity, 1.0);
+        assert_eq!(animator.sample(0.25).intensity, 0.0);
+        assert_eq!(animator.sample(0.9).intensity, 0.0);
+        // the next cycle starts on again
+        assert_eq!(animator.sample(1.0).intensity, 1.0);
+    }
+
+    #[test]
+    fn strobe_with_no_duty_cycle_is_always_off() {
+        let animator = animator(strobe(0.0), 0);
+
+        for time in [0.0, 0.5, 1.0, 1.5] {
+            assert_eq!(animator.sample(time).intensity, 0.0);
+        }
+    }
+
+    #[test]
+    fn strobe_with_a_full_duty_cycle_is_always_on() {
+        let animator = animator(strobe(1.0), 0);
+
+        for time in [0.0, 0.5, 0.999, 1.5] {
+            assert_eq!(animator.sample(time).intensity, 1.0);
+        }
+    }
+
+    #[test]
+    fn color_cycle_blends_through_the_colors_and_wraps() {
+        let animator = animator(color_cycle(vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]), 0);
+
+        assert_color_near(animator.sample(0.0).color, [1.0, 0.0, 0.0]);
+        assert_color_near(animator.sample(0.5).color, [0.5, 0.5, 0.0]);
+        assert_color_near(animator.sample(1.0).color, [0.0, 1.0, 0.0]);
+        // the last color blends back into the first
+        assert_color_near(animator.sample(1.5).color, [0.5, 0.5, 0.0]);
+        assert_color_near(animator.sample(2.0).color, [1.0, 0.0, 0.0]);
+        assert_color_near(animator.sample(-1.0).color, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn color_cycle_without_colors_leaves_the_color() {
+        let animator = animator(color_cycle(vec![]), 0);
+
+        assert_eq!(animator.sample(0.7), animator.get_base());
+    }
+
+    #[test]
+    fn position_jitter_is_ignored_without_a_position() {
+        let mut animator = LightAnimator::new(&test_light(None), 5);
+        animator.add_modifier(LightModifier::PositionJitter {
+            amount: [10.0, 10.0, 10.0],
+            speed: 4.0,
+        });
+
+        for time in [0.0, 0.3, 1.7] {
+            assert_eq!(animator.sample(time), animator.get_base());
+        }
+    }
+
+    #[test]
+    fn position_jitter_stays_within_the_amount() {
+        let amount = [2.0, 3.0, 0.5];
+        let animator = animator(LightModifier::PositionJitter { amount, speed: 4.0 }, 5);
+        let base = animator.get_base().position.unwrap();
+
+        for frame in 0..200 {
+            let position = animator.sample(frame as f32 * 0.05).position.unwrap();
+            for ((axis, base), amount) in position.iter().zip(base).zip(amount) {
+                assert!((axis - base).abs() <= amount);
+            }
+        }
+    }
+}
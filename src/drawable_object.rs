@@ -1,6 +1,9 @@
+use crate::gbuffer::GBuffer;
+use crate::lights::PointLight;
 use crate::load_image;
 use crate::shaders::draw_generate_normals;
 use crate::shaders::FULL_SCREEN_QUAD;
+use crate::Camera;
 use crate::LumenpyxProgram;
 use crate::Transform;
 use crate::DEFAULT_BEHAVIOR;
@@ -8,12 +11,151 @@ use glium;
 use glium::uniform;
 use glium::Surface;
 
+/// The light layers drawables are on if they don't choose any, layer 0
+pub const DEFAULT_LIGHT_LAYERS: u8 = 1;
+
+/// How a drawable's albedo is blended with what is already drawn beneath it.
+/// Whatever the mode, pixels too see through to have a surface of their own
+/// keep the height, material and normal beneath them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// the drawable covers what is beneath it by its alpha
+    #[default]
+    Normal,
+    /// the color is added to what is beneath it, for fire and magic
+    Additive,
+    /// what is beneath is darkened by the color, for tinted glass and smoke
+    Multiply,
+    /// what is beneath is brightened by the color, the opposite of multiply
+    Screen,
+}
+
+impl BlendMode {
+    /// the index of the mode in gbuffer_blend.glsl
+    pub(crate) fn shader_index(self) -> i32 {
+        match self {
+            BlendMode::Normal => 0,
+            BlendMode::Additive => 1,
+            BlendMode::Multiply => 2,
+            BlendMode::Screen => 3,
+        }
+    }
+
+    /// the blending of the albedo, the color is made ready for it by blend_albedo in gbuffer_blend.glsl
+    pub(crate) fn albedo_blend(self) -> glium::Blend {
+        let (source, destination) = match self {
+            BlendMode::Normal => (
+                glium::LinearBlendingFactor::SourceAlpha,
+                glium::LinearBlendingFactor::OneMinusSourceAlpha,
+            ),
+            BlendMode::Additive => (
+                glium::LinearBlendingFactor::One,
+                glium::LinearBlendingFactor::One,
+            ),
+            BlendMode::Multiply => (
+                glium::LinearBlendingFactor::DestinationColor,
+                glium::LinearBlendingFactor::Zero,
+            ),
+            BlendMode::Screen => (
+                glium::LinearBlendingFactor::One,
+                glium::LinearBlendingFactor::OneMinusSourceColor,
+            ),
+        };
+
+        glium::Blend {
+            color: glium::BlendingFunction::Addition {
+                source,
+                destination,
+            },
+            // the alpha is how much is covered, in every mode
+            alpha: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::One,
+                destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Everything a drawable needs to draw itself
+pub struct DrawContext<'a, 'b> {
+    pub program: &'a LumenpyxProgram,
+    /// The textures to draw into
+    pub gbuffer: &'a mut GBuffer<'b>,
+    /// The position of the object, already scaled for the resolution and moved by the camera
+    pub transform_matrix: [[f32; 4]; 4],
+    pub camera: &'a Camera,
+    /// The size of the gbuffer in pixels
+    pub resolution: [u32; 2],
+    /// Seconds since the program started
+    pub time: f32,
+}
+
 /// A trait for objects that can be drawn to the screen.
 /// Every primitive implements this trait.
 /// If you want to create a custom object, you will need to implement this trait.
 /// If you do, use the tutorial here: https://github.com/ABC-Engine/lumenpyx/wiki/Creating-custom-drawable-objects
 pub trait Drawable {
-    /// Draw the object to the screen
+    /// Draw the object into the gbuffer
+    /// Write the albedo, height, material and normal in one draw call with context.gbuffer.all_channels(),
+    /// or use context.gbuffer.separate_channels() to draw each channel in its own pass
+    fn draw(&self, context: &mut DrawContext);
+
+    /// Load the shaders for the object
+    /// This is called every frame, so make sure to check
+    /// if the shader is already loaded or your performance will suffer
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram);
+
+    fn get_position(&self) -> [[f32; 4]; 4];
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        0.5
+    }
+
+    /// Draw the shadow strength of every pixel the object covers into context.gbuffer.shadow_strength(),
+    /// write r and a = shadow strength, g = light layers / 255.0 and b = how much light the pixel receives.
+    /// This is called before draw. Return false to have it filled in from get_recieve_shadows_strength
+    /// and get_light_layers instead, which is the default, but it works by comparing the albedo
+    /// before and after the object is drawn, so it costs a copy of the albedo and misses any pixel
+    /// the object draws the same color as what was beneath it
+    fn draw_recieve_shadows(&self, _context: &mut DrawContext) -> bool {
+        false
+    }
+
+    /// The light layers the object is on as a bitmask, bit i is layer i.
+    /// Only lights with a matching bit in their mask light the object, by default it is on layer 0
+    fn get_light_layers(&self) -> u8 {
+        DEFAULT_LIGHT_LAYERS
+    }
+
+    /// How the object is blended with what is beneath it, see GBuffer::draw_blended.
+    /// Drawables that draw into the gbuffer themselves have to use it for this to do anything
+    fn get_blend_mode(&self) -> BlendMode {
+        BlendMode::Normal
+    }
+
+    /// Draw the glowing parts of the object into context.gbuffer.emissive(),
+    /// this is added after lighting so it is never shadowed.
+    /// Non glowing pixels should be drawn black so they cover the glow of anything beneath them.
+    /// By default nothing is drawn
+    fn draw_emissive(&self, _context: &mut DrawContext) {}
+
+    /// Lights that are cast by the glowing parts of the object.
+    /// The transform matrix is already scaled for the resolution, but not moved by the camera.
+    /// These are drawn with the rest of the lights, by default there are none
+    fn get_emissive_lights(
+        &self,
+        _program: &LumenpyxProgram,
+        _transform_matrix: [[f32; 4]; 4],
+    ) -> Vec<PointLight> {
+        vec![]
+    }
+}
+
+/// The Drawable trait from before DrawContext, with a framebuffer for each channel.
+/// Existing drawables can implement this instead of Drawable to keep working unchanged,
+/// every LegacyDrawable is also a Drawable
+pub trait LegacyDrawable {
     fn draw(
         &self,
         program: &LumenpyxProgram,
@@ -24,9 +166,6 @@ pub trait Drawable {
         normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     );
 
-    /// Load the shaders for the object
-    /// This is called every frame, so make sure to check
-    /// if the shader is already loaded or your performance will suffer
     fn try_load_shaders(&self, program: &mut LumenpyxProgram);
 
     fn get_position(&self) -> [[f32; 4]; 4];
@@ -34,4 +173,132 @@ pub trait Drawable {
     fn get_recieve_shadows_strength(&self) -> f32 {
         0.5
     }
+
+    fn draw_emissive(
+        &self,
+        _program: &LumenpyxProgram,
+        _transform_matrix: [[f32; 4]; 4],
+        _emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+    }
+
+    fn get_emissive_lights(
+        &self,
+        _program: &LumenpyxProgram,
+        _transform_matrix: [[f32; 4]; 4],
+    ) -> Vec<PointLight> {
+        vec![]
+    }
+}
+
+impl<T: LegacyDrawable> Drawable for T {
+    fn draw(&self, context: &mut DrawContext) {
+        let (albedo, height, roughness, normal) = context.gbuffer.separate_channels();
+        LegacyDrawable::draw(
+            self,
+            context.program,
+            context.transform_matrix,
+            albedo,
+            height,
+            roughness,
+            normal,
+        );
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        LegacyDrawable::try_load_shaders(self, program);
+    }
+
+    fn get_position(&self) -> [[f32; 4]; 4] {
+        LegacyDrawable::get_position(self)
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        LegacyDrawable::get_recieve_shadows_strength(self)
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        LegacyDrawable::draw_emissive(
+            self,
+            context.program,
+            context.transform_matrix,
+            context.gbuffer.emissive(),
+        );
+    }
+
+    fn get_emissive_lights(
+        &self,
+        program: &LumenpyxProgram,
+        transform_matrix: [[f32; 4]; 4],
+    ) -> Vec<PointLight> {
+        LegacyDrawable::get_emissive_lights(self, program, transform_matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color_factors(
+        mode: BlendMode,
+    ) -> (glium::LinearBlendingFactor, glium::LinearBlendingFactor) {
+        match mode.albedo_blend().color {
+            glium::BlendingFunction::Addition {
+                source,
+                destination,
+            } => (source, destination),
+            _ => panic!("the albedo is always blended by addition"),
+        }
+    }
+
+    #[test]
+    fn normal_is_the_default() {
+        assert_eq!(BlendMode::default(), BlendMode::Normal);
+    }
+
+    #[test]
+    fn shader_indices_are_distinct() {
+        let indices = [
+            BlendMode::Normal,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Screen,
+        ]
+        .map(BlendMode::shader_index);
+
+        assert_eq!(indices, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn color_factors_match_the_mode() {
+        use glium::LinearBlendingFactor::*;
+
+        assert_eq!(
+            color_factors(BlendMode::Normal),
+            (SourceAlpha, OneMinusSourceAlpha)
+        );
+        assert_eq!(color_factors(BlendMode::Additive), (One, One));
+        assert_eq!(color_factors(BlendMode::Multiply), (DestinationColor, Zero));
+        assert_eq!(color_factors(BlendMode::Screen), (One, OneMinusSourceColor));
+    }
+
+    #[test]
+    fn alpha_is_coverage_in_every_mode() {
+        for mode in [
+            BlendMode::Normal,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Screen,
+        ] {
+            let blend = mode.albedo_blend();
+            assert_eq!(
+                blend.alpha,
+                glium::BlendingFunction::Addition {
+                    source: glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
+                }
+            );
+            assert_eq!(blend.constant_value, (0.0, 0.0, 0.0, 0.0));
+        }
+    }
 }
@@ -0,0 +1,265 @@
+use crate::drawable_object::BlendMode;
+use crate::LumenpyxProgram;
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::glutin::surface::WindowSurface;
+use glium::texture::Texture2d;
+use glium::uniforms::{UniformValue, Uniforms};
+use glium::Surface;
+
+/// The names of the fragment shader outputs for each channel when drawing to GBuffer::all_channels
+/// a shader that writes every channel at once declares
+/// `out vec4 albedo; out vec4 height; out vec4 material; out vec4 normal;`
+pub const GBUFFER_OUTPUTS: [&str; 4] = ["albedo", "height", "material", "normal"];
+
+/// A texture in the gbuffer, more channels may be added so match on it with a wildcard
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GBufferChannel {
+    Albedo,
+    Height,
+    Material,
+    Normal,
+    Emissive,
+    ShadowStrength,
+}
+
+/// The textures every drawable draws into before lighting
+/// draw to all_channels to write the albedo, height, material and normal in one draw call,
+/// or to the separate channels for drawables that need a pass per channel
+pub struct GBuffer<'a> {
+    all_channels: MultiOutputFrameBuffer<'a>,
+    albedo: SimpleFrameBuffer<'a>,
+    height: SimpleFrameBuffer<'a>,
+    material: SimpleFrameBuffer<'a>,
+    normal: SimpleFrameBuffer<'a>,
+    emissive: SimpleFrameBuffer<'a>,
+    shadow_strength: SimpleFrameBuffer<'a>,
+    dimensions: [u32; 2],
+}
+
+impl<'a> GBuffer<'a> {
+    /// all the textures must be the same size
+    pub(crate) fn new(
+        display: &glium::Display<WindowSurface>,
+        albedo: &'a Texture2d,
+        height: &'a Texture2d,
+        material: &'a Texture2d,
+        normal: &'a Texture2d,
+        emissive: &'a Texture2d,
+        shadow_strength: &'a Texture2d,
+    ) -> GBuffer<'a> {
+        let outputs = [
+            (GBUFFER_OUTPUTS[0], albedo),
+            (GBUFFER_OUTPUTS[1], height),
+            (GBUFFER_OUTPUTS[2], material),
+            (GBUFFER_OUTPUTS[3], normal),
+        ];
+        let all_channels = MultiOutputFrameBuffer::new(display, outputs).unwrap();
+
+        GBuffer {
+            all_channels,
+            albedo: SimpleFrameBuffer::new(display, albedo).unwrap(),
+            height: SimpleFrameBuffer::new(display, height).unwrap(),
+            material: SimpleFrameBuffer::new(display, material).unwrap(),
+            normal: SimpleFrameBuffer::new(display, normal).unwrap(),
+            emissive: SimpleFrameBuffer::new(display, emissive).unwrap(),
+            shadow_strength: SimpleFrameBuffer::new(display, shadow_strength).unwrap(),
+            dimensions: [albedo.width(), albedo.height()],
+        }
+    }
+
+    /// Every channel at once, the outputs of the shader are named in GBUFFER_OUTPUTS
+    pub fn all_channels(&mut self) -> &mut MultiOutputFrameBuffer<'a> {
+        &mut self.all_channels
+    }
+
+    /// The color of every pixel, pixels with an alpha of 0.0 are empty
+    pub fn albedo(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.albedo
+    }
+
+    /// The height of every pixel in the red channel, from 0.0 - 1.0
+    pub fn height(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.height
+    }
+
+    /// r = reflectivity, g = roughness, b = metalness
+    pub fn material(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.material
+    }
+
+    /// The normal of every pixel, (0.0, 0.0, 1.0) faces straight up
+    pub fn normal(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.normal
+    }
+
+    /// The glow of every pixel, added after lighting so it is never shadowed
+    pub fn emissive(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.emissive
+    }
+
+    /// r and a = shadow strength, g = light layers / 255.0, b = how much light the pixel receives
+    pub fn shadow_strength(&mut self) -> &mut SimpleFrameBuffer<'a> {
+        &mut self.shadow_strength
+    }
+
+    /// Any channel by name
+    pub fn channel(&mut self, channel: GBufferChannel) -> &mut SimpleFrameBuffer<'a> {
+        match channel {
+            GBufferChannel::Albedo => &mut self.albedo,
+            GBufferChannel::Height => &mut self.height,
+            GBufferChannel::Material => &mut self.material,
+            GBufferChannel::Normal => &mut self.normal,
+            GBufferChannel::Emissive => &mut self.emissive,
+            GBufferChannel::ShadowStrength => &mut self.shadow_strength,
+        }
+    }
+
+    /// The albedo, height, material and normal channels at the same time,
+    /// for drawables that draw each channel in its own pass
+    pub fn separate_channels(
+        &mut self,
+    ) -> (
+        &mut SimpleFrameBuffer<'a>,
+        &mut SimpleFrameBuffer<'a>,
+        &mut SimpleFrameBuffer<'a>,
+        &mut SimpleFrameBuffer<'a>,
+    ) {
+        (
+            &mut self.albedo,
+            &mut self.height,
+            &mut self.material,
+            &mut self.normal,
+        )
+    }
+
+    /// The size of the channels in pixels
+    pub fn get_dimensions(&self) -> [u32; 2] {
+        self.dimensions
+    }
+
+    /// Draw a shader over the whole screen into the albedo, height, material and normal
+    /// with the blending of the blend mode.
+    /// The shader has to `#include "gbuffer_blend.glsl"` (see shaders::preprocess_shader)
+    /// and write its outputs with write_gbuffer, the blend_mode, write_albedo and write_surface uniforms are set here.
+    /// Modes other than normal draw the albedo in a second pass
+    pub fn draw_blended<U: Uniforms>(
+        &mut self,
+        program: &LumenpyxProgram,
+        shader: &glium::Program,
+        uniforms: &U,
+        blend_mode: BlendMode,
+    ) {
+        for pass in blend_passes(blend_mode) {
+            self.all_channels
+                .draw(
+                    program.get_full_screen_quad(),
+                    program.indices,
+                    shader,
+                    &BlendUniforms {
+                        uniforms,
+                        blend_mode: blend_mode.shader_index(),
+                        pass,
+                    },
+                    &glium::DrawParameters {
+                        blend: pass.blend,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+    }
+}
+
+/// one draw of GBuffer::draw_blended, the channels it doesn't write keep what is beneath them
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BlendPass {
+    albedo: bool,
+    /// the height, material and normal, only where the pixel is opaque enough to have a surface
+    surface: bool,
+    blend: glium::Blend,
+}
+
+/// the normal mode writes everything in one pass,
+/// the others can't blend the surface the way they blend the color so it gets a pass of its own first
+fn blend_passes(blend_mode: BlendMode) -> Vec<BlendPass> {
+    match blend_mode {
+        BlendMode::Normal => vec![BlendPass {
+            albedo: true,
+            surface: true,
+            blend: BlendMode::Normal.albedo_blend(),
+        }],
+        _ => vec![
+            BlendPass {
+                albedo: false,
+                surface: true,
+                blend: BlendMode::Normal.albedo_blend(),
+            },
+            BlendPass {
+                albedo: true,
+                surface: false,
+                blend: blend_mode.albedo_blend(),
+            },
+        ],
+    }
+}
+
+/// the uniforms of a drawable with the ones gbuffer_blend.glsl needs added
+struct BlendUniforms<'a, U: Uniforms> {
+    uniforms: &'a U,
+    blend_mode: i32,
+    pass: BlendPass,
+}
+
+impl<'a, U: Uniforms> Uniforms for BlendUniforms<'a, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        output("blend_mode", UniformValue::SignedInt(self.blend_mode));
+        output("write_albedo", UniformValue::Bool(self.pass.albedo));
+        output("write_surface", UniformValue::Bool(self.pass.surface));
+        self.uniforms.visit_values(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_writes_every_channel_in_one_pass() {
+        assert_eq!(
+            blend_passes(BlendMode::Normal),
+            vec![BlendPass {
+                albedo: true,
+                surface: true,
+                blend: BlendMode::Normal.albedo_blend(),
+            }]
+        );
+    }
+
+    #[test]
+    fn other_modes_blend_only_the_albedo() {
+        for mode in [BlendMode::Additive, BlendMode::Multiply, BlendMode::Screen] {
+            let passes = blend_passes(mode);
+
+            // the surface covers what is beneath it like the normal mode and leaves the albedo alone
+            assert_eq!(
+                passes[0],
+                BlendPass {
+                    albedo: false,
+                    surface: true,
+                    blend: BlendMode::Normal.albedo_blend(),
+                }
+            );
+            // then the albedo is blended by the mode and the surface is left alone
+            assert_eq!(
+                passes[1],
+                BlendPass {
+                    albedo: true,
+                    surface: false,
+                    blend: mode.albedo_blend(),
+                }
+            );
+            assert_eq!(passes.len(), 2);
+        }
+    }
+}
@@ -13,11 +13,16 @@ use shaders::*;
 /// This module contains all the objects that can be drawn in the program
 /// As well as containing the trait that all drawable objects must implement
 pub mod drawable_object;
+/// This module contains the textures drawable objects draw into
+pub mod gbuffer;
 use drawable_object::*;
 use rustc_hash::FxHashMap;
+/// This module contains the time driven modifiers for animating lights
+pub mod animation;
 /// This module contains all the lights that can be used in the program
 /// As well as containing the trait that all lights must implement
 pub mod lights;
+use lights::LightDrawable;
 
 // include the whole lumenpyx.wiki folder into the documentation
 #[doc = include_str!("../lumenpyx wiki/Home.md")]
@@ -41,6 +46,14 @@ pub(crate) const DEFAULT_BEHAVIOR: glium::uniforms::SamplerBehavior =
         depth_texture_comparison: None,
     };
 
+/// The height texture holds a max height pyramid in its mipmaps,
+/// a mipmap filter is needed for the levels above the base to be readable at all
+pub(crate) const HEIGHT_MIPMAP_BEHAVIOR: glium::uniforms::SamplerBehavior =
+    glium::uniforms::SamplerBehavior {
+        minify_filter: glium::uniforms::MinifySamplerFilter::NearestMipmapNearest,
+        ..DEFAULT_BEHAVIOR
+    };
+
 /// The debug option is used to determine what to display on the screen
 pub enum DebugOption {
     /// Display the final image
@@ -49,12 +62,17 @@ pub enum DebugOption {
     Albedo,
     /// Display the height texture
     Height,
-    /// Display the roughness texture
+    /// Display the material texture
+    /// (reflectivity, roughness and metalness in the red, green and blue channels)
     Roughness,
     /// Display the normal texture
     Normal,
     /// Display the internal shadow strength texture
     ShadowStrength,
+    /// Display the emissive texture
+    Emissive,
+    /// Display the ambient occlusion texture, white is fully open
+    AmbientOcclusion,
 }
 
 impl Default for DebugOption {
@@ -75,12 +93,26 @@ pub struct LumenpyxProgram {
     dimensions: [u32; 2],
     pub debug: DebugOption,
     pub render_settings: RenderSettings,
+    render_stats: RenderStats,
+    // the bounds the light being drawn is limited to, see get_light_draw_parameters
+    light_scissor: std::cell::Cell<Option<glium::Rect>>,
+    full_screen_quad: glium::VertexBuffer<Vertex>,
+    // the cookies and falloff gradients of the lights, the first is a 1x1 white texture
+    // bound in place of optional textures that aren't set
+    light_textures: Vec<glium::texture::Texture2d>,
+    // the per light data of the batched point lights, rewritten for every pass
+    batched_light_data: glium::texture::Texture2d,
+    // when the program was made, for the time in DrawContext and LightContext
+    creation_time: std::time::Instant,
 }
 
 impl LumenpyxProgram {
     /// Create a new program with the given resolution and name
     pub fn new(resolution: [u32; 2], name: &str) -> (LumenpyxProgram, EventLoop<()>) {
         let (event_loop, window, display, indices) = setup_program();
+        let full_screen_quad = glium::VertexBuffer::new(&display, &FULL_SCREEN_QUAD).unwrap();
+        let blank_texture = primitives::Texture::from([1.0, 1.0, 1.0, 1.0]).to_texture2d(&display);
+        let batched_light_data = lights::new_batched_light_data(&display);
 
         let mut program = LumenpyxProgram {
             window,
@@ -89,10 +121,13 @@ impl LumenpyxProgram {
             shaders: FxHashMap::default(),
             dimensions: resolution,
             debug: DebugOption::None,
-            render_settings: RenderSettings {
-                shadows: true,
-                reflections: true,
-            },
+            render_settings: RenderSettings::default(),
+            render_stats: RenderStats::default(),
+            light_scissor: std::cell::Cell::new(None),
+            full_screen_quad,
+            light_textures: vec![blank_texture],
+            batched_light_data,
+            creation_time: std::time::Instant::now(),
         };
 
         program.set_name(name);
@@ -132,6 +167,49 @@ impl LumenpyxProgram {
         self.render_settings = settings;
     }
 
+    /// Get the counts from the last frame that was drawn
+    pub fn get_render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    /// Get the draw parameters a light should draw with,
+    /// this uses the light blending and limits the drawing to the bounds of the light being drawn
+    pub fn get_light_draw_parameters(&self) -> glium::DrawParameters<'static> {
+        glium::DrawParameters {
+            blend: lights::DEFAULT_LIGHT_BLENDING,
+            scissor: self.light_scissor.get(),
+            ..Default::default()
+        }
+    }
+
+    /// The vertices of a quad covering the whole framebuffer, made once so
+    /// drawables don't need a new vertex buffer for every draw
+    pub fn get_full_screen_quad(&self) -> &glium::VertexBuffer<Vertex> {
+        &self.full_screen_quad
+    }
+
+    /// a 1x1 white texture, bound in place of optional textures that aren't set
+    pub(crate) fn get_blank_texture(&self) -> &glium::texture::Texture2d {
+        self.get_light_texture(lights::LightTextureHandle::BLANK)
+    }
+
+    /// Keep a texture for the lights to use, the lights only hold the handle so they stay cheap to copy.
+    /// The texture lives as long as the program
+    pub(crate) fn add_light_texture(
+        &mut self,
+        texture: glium::texture::Texture2d,
+    ) -> lights::LightTextureHandle {
+        self.light_textures.push(texture);
+        lights::LightTextureHandle(self.light_textures.len() - 1)
+    }
+
+    pub(crate) fn get_light_texture(
+        &self,
+        handle: lights::LightTextureHandle,
+    ) -> &glium::texture::Texture2d {
+        &self.light_textures[handle.0]
+    }
+
     /// Set the resolution of the program
     pub fn set_resolution(&mut self, resolution: [u32; 2]) {
         self.dimensions = resolution;
@@ -304,7 +382,161 @@ impl Camera {
 
 pub struct RenderSettings {
     pub shadows: bool,
+    /// How many traces each pixel averages for soft shadows,
+    /// 1 gives hard shadows and more gives smoother penumbras at a higher cost
+    pub shadow_samples: u32,
     pub reflections: bool,
+    /// The resolution the lights and shadows are worked out at,
+    /// anything lower than full is upsampled while keeping the edges of the height and normal maps
+    pub light_resolution: LightResolution,
+    /// How bright a light has to be to count, lights are only drawn where they are brighter than this
+    /// and lights that are dimmer than this everywhere on screen are skipped, 0.0 draws every light everywhere
+    pub light_cutoff: f32,
+    /// Shade all the point lights together in as few passes as possible instead of one pass per light
+    pub batch_point_lights: bool,
+    /// Blur the emissive texture and add it on top of the image, None disables bloom
+    pub bloom: Option<BloomSettings>,
+    /// The light that reaches every pixel no matter where the lights are
+    /// this is only used when shadows are on, black disables it
+    pub ambient_light: [f32; 3],
+    /// Darken the ambient and bounced light in the creases of the height map, None disables ambient occlusion
+    pub ambient_occlusion: Option<AmbientOcclusionSettings>,
+    /// Bounce the direct light off of nearby pixels, None disables global illumination
+    pub global_illumination: Option<GlobalIlluminationSettings>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            shadows: true,
+            shadow_samples: 4,
+            reflections: true,
+            light_resolution: LightResolution::Full,
+            light_cutoff: 1.0 / 255.0,
+            batch_point_lights: true,
+            bloom: None,
+            ambient_light: [0.0, 0.0, 0.0],
+            ambient_occlusion: None,
+            global_illumination: None,
+        }
+    }
+}
+
+/// The resolution the lights and shadows are worked out at compared to the rest of the image
+/// lower resolutions are much faster, the albedo is still full resolution so the edges stay crisp
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum LightResolution {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl LightResolution {
+    /// how many full resolution pixels wide one light pixel is
+    pub(crate) fn divisor(self) -> u32 {
+        match self {
+            LightResolution::Full => 1,
+            LightResolution::Half => 2,
+            LightResolution::Quarter => 4,
+        }
+    }
+}
+
+/// Counts from the last frame that was drawn, useful for finding out where the frame time goes
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RenderStats {
+    /// How many point lights were shaded in the batched passes
+    pub batched_point_lights: usize,
+    /// How many full screen passes the batched point lights took,
+    /// every pass shades up to lights::MAX_BATCHED_POINT_LIGHTS_PER_PASS lights
+    pub batched_light_passes: usize,
+    /// How many lights were drawn one at a time with their own full screen pass
+    pub individual_lights: usize,
+    /// How many lights were skipped because they couldn't reach anything on screen
+    pub culled_lights: usize,
+    /// How many of the individually drawn lights were only drawn inside their bounds
+    pub scissored_lights: usize,
+}
+
+/// The settings for the bloom pass, only emissive pixels bloom
+#[derive(Copy, Clone)]
+pub struct BloomSettings {
+    /// The radius of the blur in pixels
+    pub radius: u32,
+    /// How bright the bloom is compared to the emissive color
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            radius: 4,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// The settings for the ambient occlusion pass.
+/// Pixels surrounded by higher pixels get less of the ambient and bounced light
+#[derive(Copy, Clone)]
+pub struct AmbientOcclusionSettings {
+    /// How far away a pixel can be and still occlude in pixels
+    pub radius: f32,
+    /// How many nearby pixels are checked
+    pub samples: u32,
+    /// How dark the occluded areas get
+    pub strength: f32,
+}
+
+impl Default for AmbientOcclusionSettings {
+    fn default() -> Self {
+        AmbientOcclusionSettings {
+            radius: 8.0,
+            samples: 12,
+            strength: 1.0,
+        }
+    }
+}
+
+/// The settings for the global illumination pass.
+/// Every pixel traces rays over the height map and gathers the light of the pixels it can see,
+/// so light bounces into areas that no light reaches directly
+#[derive(Copy, Clone)]
+pub struct GlobalIlluminationSettings {
+    /// How many directions each pixel gathers light from
+    pub rays: u32,
+    /// How many samples are taken along each ray
+    pub steps: u32,
+    /// How far the rays go in pixels
+    pub radius: f32,
+    /// How bright the bounced light is
+    pub intensity: f32,
+    /// How many times the light bounces, every bounce is another full screen pass
+    pub bounces: u32,
+}
+
+impl Default for GlobalIlluminationSettings {
+    fn default() -> Self {
+        GlobalIlluminationSettings {
+            rays: 8,
+            steps: 8,
+            radius: 32.0,
+            intensity: 1.0,
+            bounces: 1,
+        }
+    }
+}
+
+/// scale a transform so it isn't stretched by the aspect ratio of the resolution
+fn scale_to_resolution(matrix: [[f32; 4]; 4], dimensions: [u32; 2]) -> [[f32; 4]; 4] {
+    let mut new_matrix = matrix;
+    if dimensions[0] > dimensions[1] {
+        new_matrix[0][0] *= dimensions[1] as f32 / dimensions[0] as f32;
+    } else {
+        new_matrix[1][1] *= dimensions[0] as f32 / dimensions[1] as f32;
+    }
+    new_matrix
 }
 
 /// Draw everything to the screen
@@ -322,21 +554,47 @@ pub fn draw_all(
         light.try_load_shaders(program);
     }
 
+    // lights made from the glowing pixels of the drawables, these are drawn with the rest of the lights
+    let emissive_lights: Vec<lights::PointLight> = if program.render_settings.shadows {
+        drawables
+            .iter()
+            .flat_map(|drawable| {
+                let matrix = scale_to_resolution(drawable.get_position(), program.dimensions);
+                drawable.get_emissive_lights(program, matrix)
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+    for light in &emissive_lights {
+        light.try_load_shaders(program);
+    }
+
     /*
     STEP 1:
         render every albedo to a texture
         render every height to a texture
-        render every roughness to a texture
+        render every material (reflectivity, roughness, metalness) to a texture
         render every normal to a texture
+        render every emissive to a texture
 
         find the difference between the last frame and this frame
         use this to color the different pixels with the shadow strength
+
+        build a max height pyramid in the mipmaps of the height texture
+        so shadows and reflections can skip over big empty areas
     STEP 2:
         take the textures and feed it into a lighting shader
         we do this for every light and then blend the results together
+        optionally at a lower resolution, upsampled along the edges of the height and normal maps
+        then add the ambient light, darkened by the ambient occlusion from the height map
+        then add the emissive on top, so it is never shadowed
+        optionally gather the light bouncing off of nearby pixels for global illumination
+        optionally blur the emissive and add it again for bloom
     STEP 3:
         take the result and feed it into a reflection shader
-        it uses screen space reflections and lerps between the reflection and the original image based on the roughness
+        it uses screen space reflections and lerps between the reflection and the original image based on the reflectivity
+        rough surfaces blur the reflection and metals tint it with their albedo
     STEP 4:
         upscale the result to the screen size
     */
@@ -344,6 +602,8 @@ pub fn draw_all(
     let display = &program.display;
     let debug = &program.debug;
     let render_settings = &program.render_settings;
+    let time = program.creation_time.elapsed().as_secs_f32();
+    let mut render_stats = RenderStats::default();
 
     let albedo_texture = glium::texture::Texture2d::empty_with_format(
         display,
@@ -354,10 +614,11 @@ pub fn draw_all(
     )
     .unwrap();
 
+    // the mipmaps hold the max height of the pixels below them, for faster shadows and reflections
     let height_texture = glium::texture::Texture2d::empty_with_format(
         display,
         glium::texture::UncompressedFloatFormat::U8U8U8U8,
-        glium::texture::MipmapsOption::NoMipmap,
+        glium::texture::MipmapsOption::EmptyMipmaps,
         program.dimensions[0],
         program.dimensions[1],
     )
@@ -381,6 +642,16 @@ pub fn draw_all(
     )
     .unwrap();
 
+    // this is a float texture so emissive colors can be brighter than 1.0
+    let emissive_texture = glium::texture::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::F16F16F16F16,
+        glium::texture::MipmapsOption::NoMipmap,
+        program.dimensions[0],
+        program.dimensions[1],
+    )
+    .unwrap();
+
     let shadow_strength_texture = glium::texture::Texture2d::empty_with_format(
         display,
         glium::texture::UncompressedFloatFormat::U8U8U8U8,
@@ -408,57 +679,48 @@ pub fn draw_all(
 
         let this_drawable_sampler = glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR);
 
-        let mut albedo_framebuffer =
-            glium::framebuffer::SimpleFrameBuffer::new(display, &albedo_texture).unwrap();
-
-        let mut height_framebuffer =
-            glium::framebuffer::SimpleFrameBuffer::new(display, &height_texture).unwrap();
-
-        let mut roughness_framebuffer =
-            glium::framebuffer::SimpleFrameBuffer::new(display, &roughness_texture).unwrap();
-
-        let mut normal_framebuffer =
-            glium::framebuffer::SimpleFrameBuffer::new(display, &normal_texture).unwrap();
-
-        normal_framebuffer.clear_color(0.0, 0.0, 1.0, 1.0);
+        let mut gbuffer = gbuffer::GBuffer::new(
+            display,
+            &albedo_texture,
+            &height_texture,
+            &roughness_texture,
+            &normal_texture,
+            &emissive_texture,
+            &shadow_strength_texture,
+        );
 
-        let mut shadow_strength_framebuffer =
-            glium::framebuffer::SimpleFrameBuffer::new(display, &shadow_strength_texture).unwrap();
+        gbuffer.normal().clear_color(0.0, 0.0, 1.0, 1.0);
+        // nothing receives shadows until it is drawn, but every pixel receives all the light
+        gbuffer.shadow_strength().clear_color(
+            0.0,
+            drawable_object::DEFAULT_LIGHT_LAYERS as f32 / 255.0,
+            1.0,
+            0.0,
+        );
 
         for drawable in &drawables {
-            let mut new_matrix = drawable.get_position();
             // scale off the resolution
-            if program.dimensions[0] > program.dimensions[1] {
-                new_matrix[0][0] *= program.dimensions[1] as f32 / program.dimensions[0] as f32;
-            } else {
-                new_matrix[1][1] *= program.dimensions[0] as f32 / program.dimensions[1] as f32;
-            }
+            let mut new_matrix = scale_to_resolution(drawable.get_position(), program.dimensions);
             // adjust off the camera no need to translate the z, it would just mess up the height map's interaction with the light
             new_matrix[3][0] -= camera.position[0];
             new_matrix[3][1] -= camera.position[1];
 
-            drawable.draw(
+            let mut context = DrawContext {
                 program,
-                new_matrix,
-                &mut albedo_framebuffer,
-                &mut height_framebuffer,
-                &mut roughness_framebuffer,
-                &mut normal_framebuffer,
-            );
-
-            if render_settings.shadows {
-                let shadow_strength = drawable.get_recieve_shadows_strength();
-
-                shaders::draw_recieve_shadows(
-                    &mut shadow_strength_framebuffer,
-                    &program,
-                    shadow_strength,
-                    last_drawable_sampler,
-                    this_drawable_sampler,
-                );
-
-                // copy the albedo to the last drawable framebuffer
-                albedo_framebuffer.blit_whole_color_to(
+                gbuffer: &mut gbuffer,
+                transform_matrix: new_matrix,
+                camera,
+                resolution: program.dimensions,
+                time,
+            };
+
+            // drawables that write their own shadow strength don't need the albedo compared
+            let needs_recieve_shadows_diff =
+                render_settings.shadows && !drawable.draw_recieve_shadows(&mut context);
+
+            if needs_recieve_shadows_diff {
+                // copy the albedo to the last drawable framebuffer to compare against after drawing
+                context.gbuffer.albedo().blit_whole_color_to(
                     &last_drawable_framebuffer,
                     &glium::BlitTarget {
                         left: 0,
@@ -469,6 +731,50 @@ pub fn draw_all(
                     glium::uniforms::MagnifySamplerFilter::Nearest,
                 );
             }
+
+            drawable.draw(&mut context);
+
+            drawable.draw_emissive(&mut context);
+
+            if needs_recieve_shadows_diff {
+                shaders::draw_recieve_shadows(
+                    context.gbuffer.shadow_strength(),
+                    context.program,
+                    drawable.get_recieve_shadows_strength(),
+                    drawable.get_light_layers(),
+                    last_drawable_sampler,
+                    this_drawable_sampler,
+                );
+            }
+        }
+    }
+
+    shaders::generate_max_height_mipmaps(program, &height_texture);
+
+    // white means fully open, so without ambient occlusion nothing is darkened
+    let ambient_occlusion_texture = glium::texture::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::U8U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap,
+        program.dimensions[0],
+        program.dimensions[1],
+    )
+    .unwrap();
+
+    {
+        let mut ambient_occlusion_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, &ambient_occlusion_texture)
+                .unwrap();
+        ambient_occlusion_framebuffer.clear_color(1.0, 1.0, 1.0, 1.0);
+
+        if let Some(ambient_occlusion_settings) = &render_settings.ambient_occlusion {
+            shaders::draw_ambient_occlusion(
+                &mut ambient_occlusion_framebuffer,
+                program,
+                glium::uniforms::Sampler(&height_texture, HEIGHT_MIPMAP_BEHAVIOR),
+                glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR),
+                ambient_occlusion_settings,
+            );
         }
     }
 
@@ -481,35 +787,187 @@ pub fn draw_all(
     )
     .expect("Failed to create lit frame buffer");
 
-    if render_settings.shadows {
+    {
         let albedo = glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR);
-        let height_sampler = glium::uniforms::Sampler(&height_texture, DEFAULT_BEHAVIOR);
+        let height_sampler = glium::uniforms::Sampler(&height_texture, HEIGHT_MIPMAP_BEHAVIOR);
         let roughness_sampler = glium::uniforms::Sampler(&roughness_texture, DEFAULT_BEHAVIOR);
         let shadow_strength_sampler =
             glium::uniforms::Sampler(&shadow_strength_texture, DEFAULT_BEHAVIOR);
+        let normal_sampler = glium::uniforms::Sampler(&normal_texture, DEFAULT_BEHAVIOR);
+        let ambient_occlusion_sampler =
+            glium::uniforms::Sampler(&ambient_occlusion_texture, DEFAULT_BEHAVIOR);
+
+        let light_divisor = render_settings.light_resolution.divisor();
+
+        // at a lower resolution the lights only work out how much light reaches every pixel,
+        // they see a white albedo and the real albedo is multiplied in when upsampling.
+        // these have to outlive the lit framebuffer as the lights draw into either of them
+        let low_res_light_textures = if render_settings.shadows && light_divisor > 1 {
+            let coverage_texture = shaders::new_fill_alpha_texure(program, albedo, [1.0; 4]);
+            let light_texture = glium::texture::Texture2d::empty_with_format(
+                display,
+                glium::texture::UncompressedFloatFormat::F16F16F16F16,
+                glium::texture::MipmapsOption::NoMipmap,
+                program.dimensions[0].div_ceil(light_divisor),
+                program.dimensions[1].div_ceil(light_divisor),
+            )
+            .unwrap();
+            Some((coverage_texture, light_texture))
+        } else {
+            None
+        };
 
         let mut lit_framebuffer =
             glium::framebuffer::SimpleFrameBuffer::new(display, &lit_texture).unwrap();
 
-        for light in lights {
-            let mut new_matrix = light.get_transform();
-            if program.dimensions[0] > program.dimensions[1] {
-                new_matrix[0][0] *= program.dimensions[1] as f32 / program.dimensions[0] as f32;
-            } else {
-                new_matrix[1][1] *= program.dimensions[0] as f32 / program.dimensions[1] as f32;
-            }
-            // adjust off the camera no need to translate the z, it would just mess up the height map's interaction with the light
-            new_matrix[3][0] -= camera.position[0];
-            new_matrix[3][1] -= camera.position[1];
+        if render_settings.shadows {
+            let mut low_res_light_framebuffer =
+                low_res_light_textures.as_ref().map(|(_, light_texture)| {
+                    let mut framebuffer =
+                        glium::framebuffer::SimpleFrameBuffer::new(display, light_texture).unwrap();
+                    framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+                    framebuffer
+                });
+
+            let light_albedo = match &low_res_light_textures {
+                Some((coverage_texture, _)) => {
+                    glium::uniforms::Sampler(coverage_texture, DEFAULT_BEHAVIOR)
+                }
+                None => albedo,
+            };
+
+            let light_framebuffer = match &mut low_res_light_framebuffer {
+                Some(framebuffer) => framebuffer,
+                None => &mut lit_framebuffer,
+            };
+
+            let mut all_lights: Vec<&dyn lights::LightDrawable> = lights;
+            all_lights.extend(
+                emissive_lights
+                    .iter()
+                    .map(|light| light as &dyn lights::LightDrawable),
+            );
 
-            light.draw(
+            // the transform and position are set for every light as it is drawn
+            let mut light_context = lights::LightContext {
                 program,
-                new_matrix,
+                framebuffer: light_framebuffer,
+                transform_matrix: [[0.0; 4]; 4],
+                light_position: [0.0; 3],
+                albedo: light_albedo,
+                height: height_sampler,
+                material: roughness_sampler,
+                normal: normal_sampler,
+                shadow_strength: shadow_strength_sampler,
+                camera,
+                resolution: program.dimensions,
+                time,
+            };
+
+            let mut batched_point_lights = vec![];
+            for light in all_lights {
+                let mut new_matrix = scale_to_resolution(light.get_transform(), program.dimensions);
+                // adjust off the camera no need to translate the z, it would just mess up the height map's interaction with the light
+                new_matrix[3][0] -= camera.position[0];
+                new_matrix[3][1] -= camera.position[1];
+
+                let bounds = match light.get_influence_bounds(
+                    new_matrix,
+                    program.dimensions,
+                    render_settings.light_cutoff,
+                ) {
+                    Some(bounds) => match bounds.clamp_to_screen(program.dimensions) {
+                        // the bounds are in full resolution pixels
+                        Some(bounds) => Some(bounds.scaled(1.0 / light_divisor as f32)),
+                        None => {
+                            // the light can't reach anything on screen
+                            render_stats.culled_lights += 1;
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+
+                if render_settings.batch_point_lights {
+                    if let Some(point_light) = light.as_batched_point_light() {
+                        batched_point_lights.push((point_light, new_matrix, bounds));
+                        continue;
+                    }
+                }
+
+                if bounds.is_some() {
+                    render_stats.scissored_lights += 1;
+                }
+                program
+                    .light_scissor
+                    .set(bounds.map(|bounds| bounds.to_scissor()));
+
+                render_stats.individual_lights += 1;
+                light_context.transform_matrix = new_matrix;
+                light_context.light_position =
+                    lights::to_texture_position(new_matrix, [0.0, 0.0, 0.0]);
+                light.draw(&mut light_context);
+            }
+            program.light_scissor.set(None);
+
+            if !batched_point_lights.is_empty() {
+                render_stats.batched_point_lights = batched_point_lights.len();
+                render_stats.batched_light_passes =
+                    lights::draw_batched_point_lights(&mut light_context, &batched_point_lights);
+            }
+
+            if let Some((_, light_texture)) = &low_res_light_textures {
+                shaders::draw_light_upsample(
+                    &mut lit_framebuffer,
+                    program,
+                    glium::uniforms::Sampler(light_texture, DEFAULT_BEHAVIOR),
+                    albedo,
+                    height_sampler,
+                    normal_sampler,
+                );
+            }
+
+            if render_settings.ambient_light != [0.0, 0.0, 0.0] {
+                shaders::draw_ambient_light(
+                    &mut lit_framebuffer,
+                    program,
+                    albedo,
+                    ambient_occlusion_sampler,
+                    render_settings.ambient_light,
+                );
+            }
+        } else {
+            // without lighting the albedo is shown as is
+            shaders::draw_texture(&mut lit_framebuffer, program, albedo, None);
+        }
+
+        // the emissive is added after the lighting so it is never shadowed
+        let emissive_sampler = glium::uniforms::Sampler(&emissive_texture, DEFAULT_BEHAVIOR);
+        shaders::draw_texture(
+            &mut lit_framebuffer,
+            program,
+            emissive_sampler,
+            Some(lights::DEFAULT_LIGHT_BLENDING),
+        );
+
+        if let Some(global_illumination_settings) = &render_settings.global_illumination {
+            shaders::draw_global_illumination(
                 &mut lit_framebuffer,
-                height_sampler,
+                program,
+                glium::uniforms::Sampler(&lit_texture, DEFAULT_BEHAVIOR),
                 albedo,
-                roughness_sampler,
-                shadow_strength_sampler,
+                height_sampler,
+                ambient_occlusion_sampler,
+                global_illumination_settings,
+            );
+        }
+
+        if let Some(bloom_settings) = &render_settings.bloom {
+            shaders::draw_bloom(
+                &mut lit_framebuffer,
+                program,
+                emissive_sampler,
+                bloom_settings,
             );
         }
     }
@@ -525,13 +983,9 @@ pub fn draw_all(
 
     if render_settings.reflections {
         let roughness = glium::uniforms::Sampler(&roughness_texture, DEFAULT_BEHAVIOR);
-        let height = glium::uniforms::Sampler(&height_texture, DEFAULT_BEHAVIOR);
+        let height = glium::uniforms::Sampler(&height_texture, HEIGHT_MIPMAP_BEHAVIOR);
         let normal = glium::uniforms::Sampler(&normal_texture, DEFAULT_BEHAVIOR);
-        let lit_sampler = if render_settings.shadows {
-            glium::uniforms::Sampler(&lit_texture, DEFAULT_BEHAVIOR)
-        } else {
-            glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR)
-        };
+        let lit_sampler = glium::uniforms::Sampler(&lit_texture, DEFAULT_BEHAVIOR);
 
         let mut reflected_framebuffer =
             glium::framebuffer::SimpleFrameBuffer::new(display, &reflected_texture).unwrap();
@@ -539,6 +993,7 @@ pub fn draw_all(
         draw_reflections(
             camera,
             lit_sampler,
+            glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR),
             height,
             roughness,
             normal,
@@ -549,7 +1004,13 @@ pub fn draw_all(
 
     {
         let finished_texture = match debug {
-            DebugOption::None => glium::uniforms::Sampler(&reflected_texture, DEFAULT_BEHAVIOR),
+            DebugOption::None => {
+                if render_settings.reflections {
+                    glium::uniforms::Sampler(&reflected_texture, DEFAULT_BEHAVIOR)
+                } else {
+                    glium::uniforms::Sampler(&lit_texture, DEFAULT_BEHAVIOR)
+                }
+            }
             DebugOption::Albedo => glium::uniforms::Sampler(&albedo_texture, DEFAULT_BEHAVIOR),
             DebugOption::Height => glium::uniforms::Sampler(&height_texture, DEFAULT_BEHAVIOR),
             DebugOption::Roughness => {
@@ -559,7 +1020,13 @@ pub fn draw_all(
             DebugOption::ShadowStrength => {
                 glium::uniforms::Sampler(&shadow_strength_texture, DEFAULT_BEHAVIOR)
             }
+            DebugOption::Emissive => glium::uniforms::Sampler(&emissive_texture, DEFAULT_BEHAVIOR),
+            DebugOption::AmbientOcclusion => {
+                glium::uniforms::Sampler(&ambient_occlusion_texture, DEFAULT_BEHAVIOR)
+            }
         };
         draw_upscale(finished_texture, &program);
     }
+
+    program.render_stats = render_stats;
 }
@@ -1,4 +1,7 @@
-use crate::shaders::FULL_SCREEN_QUAD;
+use crate::animation::{AnimatableLight, LightState};
+use crate::primitives::Texture;
+use crate::shaders::{preprocess_shader, UnknownShaderInclude};
+use crate::Camera;
 use crate::LumenpyxProgram;
 use glium;
 use glium::framebuffer::SimpleFrameBuffer;
@@ -21,6 +24,35 @@ pub(crate) const DIRECTIONAL_LIGHT_VERTEX_SHADER_SRC: &str =
 pub(crate) const DIRECTIONAL_LIGHT_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/shading/lighting/directional_light.frag");
 
+pub(crate) const SUN_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/sun_light.vert");
+pub(crate) const SUN_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/sun_light.frag");
+
+pub(crate) const SPOT_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/spot_light.vert");
+pub(crate) const SPOT_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/spot_light.frag");
+
+pub(crate) const LINE_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/line_light.vert");
+pub(crate) const LINE_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/line_light.frag");
+
+pub(crate) const SHADOWED_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/shadowed_light.vert");
+pub(crate) const SHADOWED_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/shadowed_light.frag");
+
+pub(crate) const BATCHED_POINT_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/batched_point_light.vert");
+pub(crate) const BATCHED_POINT_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/lighting/batched_point_light.frag");
+
+/// The most point lights that are shaded in one full screen pass,
+/// any more than this are split over extra passes
+pub const MAX_BATCHED_POINT_LIGHTS_PER_PASS: usize = 64;
+
 pub const DEFAULT_LIGHT_BLENDING: Blend = glium::Blend {
     color: glium::BlendingFunction::Addition {
         source: glium::LinearBlendingFactor::One,
@@ -33,11 +65,384 @@ pub const DEFAULT_LIGHT_BLENDING: Blend = glium::Blend {
     constant_value: (0.0, 0.0, 0.0, 0.0),
 };
 
+// cookies don't repeat, past the edge of the texture they keep the color of the edge
+const COOKIE_BEHAVIOR: glium::uniforms::SamplerBehavior = glium::uniforms::SamplerBehavior {
+    minify_filter: glium::uniforms::MinifySamplerFilter::Nearest,
+    magnify_filter: glium::uniforms::MagnifySamplerFilter::Nearest,
+    max_anisotropy: 1,
+    wrap_function: (
+        glium::uniforms::SamplerWrapFunction::Clamp,
+        glium::uniforms::SamplerWrapFunction::Clamp,
+        glium::uniforms::SamplerWrapFunction::Clamp,
+    ),
+    depth_texture_comparison: None,
+};
+
+// falloff gradients blend between their texels so a short gradient still fades smoothly
+const GRADIENT_BEHAVIOR: glium::uniforms::SamplerBehavior = glium::uniforms::SamplerBehavior {
+    minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
+    magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
+    ..COOKIE_BEHAVIOR
+};
+
+/// A mask that lets a light shine on every light layer
+pub const ALL_LIGHT_LAYERS: u8 = u8::MAX;
+
+/// A texture kept by the program for the lights, like a cookie or a falloff gradient
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LightTextureHandle(pub(crate) usize);
+
+impl LightTextureHandle {
+    /// the 1x1 white texture every program starts with
+    pub(crate) const BLANK: LightTextureHandle = LightTextureHandle(0);
+}
+
+/// A mask texture projected by a light, like the shadow of a window frame,
+/// dappled light through leaves or a stained glass pattern
+/// the color of the texture tints the light and the alpha dims it
+/// the texture is kept by the program, so copying a cookie is cheap
+#[derive(Copy, Clone)]
+pub struct Cookie {
+    texture: LightTextureHandle,
+    rotation: f32,
+    scale: f32,
+}
+
+impl Cookie {
+    /// Create a new cookie from a texture
+    /// rotation is in radians, counter clockwise
+    /// scale is how big the cookie is, for point lights it is the radius in pixels it covers around the light,
+    /// for spot lights 1.0 fits it to the outer cone and for directional lights it is how far it
+    /// spreads sideways per pixel forward
+    pub fn new(
+        texture: Texture,
+        rotation: f32,
+        scale: f32,
+        program: &mut LumenpyxProgram,
+    ) -> Cookie {
+        let texture = texture.to_texture2d(&program.display);
+        Cookie {
+            texture: program.add_light_texture(texture),
+            rotation,
+            scale,
+        }
+    }
+
+    /// a white cookie that doesn't change the light, used by lights without a cookie
+    const BLANK: Cookie = Cookie {
+        texture: LightTextureHandle::BLANK,
+        rotation: 0.0,
+        scale: 1.0,
+    };
+
+    /// Set the rotation of the cookie in radians, counter clockwise
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    /// Set how big the cookie is
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+}
+
+/// How a light casts shadows
+/// the darkness of a shadow is the opacity times the shadow strength of what it falls on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// if false the light shines through everything and no shadows are traced for it
+    pub cast_shadows: bool,
+    /// how dark the shadows are in 0.0 - 1.0 range
+    pub opacity: f32,
+    /// what fully shadowed pixels are multiplied by, None is black
+    pub color: Option<[f32; 3]>,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            cast_shadows: true,
+            opacity: 1.0,
+            color: None,
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// the opacity the shaders use, 0.0 turns the shadow tracing off
+    pub(crate) fn shader_opacity(&self) -> f32 {
+        if self.cast_shadows {
+            self.opacity
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn shader_color(&self) -> [f32; 3] {
+        self.color.unwrap_or([0.0, 0.0, 0.0])
+    }
+}
+
+/// The curve a light fades out with as it gets further away
+/// used by the point, area and line lights
+/// with a range set every curve reaches exactly zero at the range,
+/// without one the curves other than inverse square reach zero at 1.0 / falloff
+#[derive(Copy, Clone, Default)]
+pub enum FalloffModel {
+    /// intensity / (1 + (distance * falloff)^2), smoothly windowed to zero at the range
+    #[default]
+    InverseSquare,
+    /// fades in a straight line
+    Linear,
+    /// fades with a smoothstep, flat near the light and at the edge
+    Smoothstep,
+    /// the same brightness all the way out
+    Constant,
+    /// read from a texture from left (at the light) to right (at the edge), the color tints the light
+    Gradient(LightTextureHandle),
+}
+
+impl FalloffModel {
+    /// Create a gradient falloff from a texture, only the middle row of the texture is used
+    pub fn gradient(texture: Texture, program: &mut LumenpyxProgram) -> FalloffModel {
+        let texture = texture.to_texture2d(&program.display);
+        FalloffModel::Gradient(program.add_light_texture(texture))
+    }
+
+    /// the number the shaders use for this model, see falloff.glsl
+    pub(crate) fn shader_index(&self) -> i32 {
+        match self {
+            FalloffModel::InverseSquare => 0,
+            FalloffModel::Linear => 1,
+            FalloffModel::Smoothstep => 2,
+            FalloffModel::Constant => 3,
+            FalloffModel::Gradient(_) => 4,
+        }
+    }
+
+    /// how far in pixels the light reaches before it is dimmer than the cutoff, None if it never is
+    pub(crate) fn reach(
+        &self,
+        intensity: f32,
+        falloff: f32,
+        range: Option<f32>,
+        cutoff: f32,
+    ) -> Option<f32> {
+        if let Some(range) = range {
+            return Some(range);
+        }
+
+        match self {
+            FalloffModel::InverseSquare => {
+                if falloff <= 0.0 || cutoff <= 0.0 {
+                    None
+                } else if intensity <= cutoff {
+                    Some(0.0)
+                } else {
+                    // solve intensity / (1 + (distance * falloff)^2) = cutoff for the distance
+                    Some((intensity / cutoff - 1.0).sqrt() / falloff)
+                }
+            }
+            _ => {
+                if falloff <= 0.0 {
+                    None
+                } else {
+                    Some(1.0 / falloff)
+                }
+            }
+        }
+    }
+}
+
+/// The area a light reaches in pixels, past this it is dimmer than the cutoff in the render settings
+/// (0, 0) is the bottom left of the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightBounds {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl LightBounds {
+    /// Create bounds from the bottom left and top right corners in pixels
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> LightBounds {
+        LightBounds { min, max }
+    }
+
+    /// Create bounds that cover a circle in pixels
+    pub fn around(center: [f32; 2], radius: f32) -> LightBounds {
+        LightBounds {
+            min: [center[0] - radius, center[1] - radius],
+            max: [center[0] + radius, center[1] + radius],
+        }
+    }
+
+    /// Grow the bounds to cover the other bounds too
+    pub fn union(self, other: LightBounds) -> LightBounds {
+        LightBounds {
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+
+    /// Scale the bounds, for drawing at a different resolution
+    pub(crate) fn scaled(self, factor: f32) -> LightBounds {
+        LightBounds {
+            min: [self.min[0] * factor, self.min[1] * factor],
+            max: [self.max[0] * factor, self.max[1] * factor],
+        }
+    }
+
+    /// Cut the bounds down to the screen, None if none of it is on screen
+    pub(crate) fn clamp_to_screen(self, resolution: [u32; 2]) -> Option<LightBounds> {
+        let min = [self.min[0].max(0.0), self.min[1].max(0.0)];
+        let max = [
+            self.max[0].min(resolution[0] as f32),
+            self.max[1].min(resolution[1] as f32),
+        ];
+
+        if max[0] <= min[0] || max[1] <= min[1] {
+            None
+        } else {
+            Some(LightBounds { min, max })
+        }
+    }
+
+    /// The scissor rectangle that covers the bounds, the bounds should already be on screen
+    pub(crate) fn to_scissor(self) -> glium::Rect {
+        let left = self.min[0].floor() as u32;
+        let bottom = self.min[1].floor() as u32;
+        glium::Rect {
+            left,
+            bottom,
+            width: self.max[0].ceil() as u32 - left,
+            height: self.max[1].ceil() as u32 - bottom,
+        }
+    }
+}
+
+/// move a point through a light's transform and from -1.0 to 1.0 to 0.0 to 1.0
+pub(crate) fn to_texture_position(matrix: [[f32; 4]; 4], point: [f32; 3]) -> [f32; 3] {
+    [
+        (matrix[0][0] * point[0] + matrix[1][0] * point[1] + matrix[3][0] + 1.0) * 0.5,
+        (matrix[0][1] * point[0] + matrix[1][1] * point[1] + matrix[3][1] + 1.0) * 0.5,
+        matrix[2][2] * point[2] + matrix[3][2],
+    ]
+}
+
+type TextureSampler<'a> = glium::uniforms::Sampler<'a, glium::texture::Texture2d>;
+
+/// Everything a light needs to draw itself
+pub struct LightContext<'a, 'b> {
+    pub program: &'a LumenpyxProgram,
+    /// The framebuffer the light adds its light to,
+    /// it can be smaller than the screen if the lights are drawn at a lower resolution
+    pub framebuffer: &'a mut SimpleFrameBuffer<'b>,
+    /// The transform of the light, already scaled for the resolution and moved by the camera
+    pub transform_matrix: [[f32; 4]; 4],
+    /// The position of the light in the textures, x and y from 0.0 - 1.0 across the screen
+    /// and z the same scale as the heightmap, this is what the built-in light shaders take as light_pos
+    pub light_position: [f32; 3],
+    /// When the lights are drawn at a lower resolution this is white wherever something was drawn,
+    /// the real albedo is multiplied in afterwards
+    pub albedo: TextureSampler<'a>,
+    /// The mipmaps hold the max height of the pixels below them,
+    /// read it with textureLod(height, coords, 0.0) so lower resolution passes still get the real height
+    pub height: TextureSampler<'a>,
+    /// r = reflectivity, g = roughness, b = metalness
+    /// the built-in lights only use the reflectivity and metalness to dim metals, they ignore the roughness
+    pub material: TextureSampler<'a>,
+    /// The normal of every pixel, (0.0, 0.0, 1.0) faces straight up.
+    /// It is stored as it is, so the negative parts of a normal read as 0.0,
+    /// the built-in lights don't shade by it, only the reflections and the light upsampling read it
+    pub normal: TextureSampler<'a>,
+    /// r and a = shadow strength, g = light layers / 255.0, b = how much light the pixel receives
+    pub shadow_strength: TextureSampler<'a>,
+    pub camera: &'a Camera,
+    /// The size of the screen in pixels
+    pub resolution: [u32; 2],
+    /// Seconds since the program started
+    pub time: f32,
+}
+
+impl<'a, 'b> LightContext<'a, 'b> {
+    /// Move a point from the light's own space into the textures, the same way as light_position
+    pub fn to_texture_position(&self, point: [f32; 3]) -> [f32; 3] {
+        to_texture_position(self.transform_matrix, point)
+    }
+
+    /// Move a position in the textures into pixels on the screen, (0, 0) is the bottom left
+    pub fn to_pixels(&self, texture_position: [f32; 2]) -> [f32; 2] {
+        [
+            texture_position[0] * self.resolution[0] as f32,
+            texture_position[1] * self.resolution[1] as f32,
+        ]
+    }
+
+    /// The position of the light in pixels on the screen, (0, 0) is the bottom left
+    pub fn light_pixel_position(&self) -> [f32; 2] {
+        self.to_pixels([self.light_position[0], self.light_position[1]])
+    }
+
+    /// Draw a shader over the whole framebuffer with the light blending,
+    /// limited to the bounds of the light
+    pub fn draw_full_screen<U: glium::uniforms::Uniforms>(
+        &mut self,
+        shader: &glium::Program,
+        uniforms: &U,
+    ) {
+        self.framebuffer
+            .draw(
+                self.program.get_full_screen_quad(),
+                self.program.indices,
+                shader,
+                uniforms,
+                &self.program.get_light_draw_parameters(),
+            )
+            .unwrap();
+    }
+}
+
 /// A trait for drawable lights
 /// This trait is used to draw lights in the scene
 /// If you want to create a custom light, you can implement this trait
-/// Follow the example here: https://github.com/ABC-Engine/lumenpyx/wiki/Creating-custom-drawable-objects
+/// Follow the example here: https://github.com/ABC-Engine/lumenpyx/wiki/Creating-Custom-Lights
 pub trait LightDrawable {
+    /// Add the light to context.framebuffer, usually with context.draw_full_screen
+    fn draw(&self, context: &mut LightContext);
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram);
+    fn get_transform(&self) -> [[f32; 4]; 4];
+    /// Return the point light if this light can be shaded in the batched point light pass
+    /// custom lights keep the default and are drawn on their own
+    fn as_batched_point_light(&self) -> Option<&PointLight> {
+        None
+    }
+
+    /// The light layers the light shines on as a bitmask, bit i is layer i.
+    /// Pixels of drawables that share no layer with the light should be skipped,
+    /// by default the light shines on every layer
+    fn get_light_layers(&self) -> u8 {
+        ALL_LIGHT_LAYERS
+    }
+    /// The area this light reaches in pixels with the given transform,
+    /// lights that are entirely off screen are skipped and the rest are only drawn inside their bounds.
+    /// cutoff is the brightness below which the light counts as dark.
+    /// None means the light can reach the whole screen, which is the default for custom lights
+    fn get_influence_bounds(
+        &self,
+        _matrix_transform: [[f32; 4]; 4],
+        _resolution: [u32; 2],
+        _cutoff: f32,
+    ) -> Option<LightBounds> {
+        None
+    }
+}
+
+/// The LightDrawable trait from before LightContext, with a sampler for each texture.
+/// Existing lights can implement this instead of LightDrawable to keep working unchanged,
+/// every LegacyLightDrawable is also a LightDrawable
+pub trait LegacyLightDrawable {
+    // the signature from before LightContext, kept as it was so existing lights don't have to change
+    #[allow(clippy::too_many_arguments)]
     fn draw(
         &self,
         program: &LumenpyxProgram,
@@ -50,6 +455,55 @@ pub trait LightDrawable {
     );
     fn try_load_shaders(&self, program: &mut LumenpyxProgram);
     fn get_transform(&self) -> [[f32; 4]; 4];
+
+    fn get_light_layers(&self) -> u8 {
+        ALL_LIGHT_LAYERS
+    }
+
+    fn get_influence_bounds(
+        &self,
+        _matrix_transform: [[f32; 4]; 4],
+        _resolution: [u32; 2],
+        _cutoff: f32,
+    ) -> Option<LightBounds> {
+        None
+    }
+}
+
+impl<T: LegacyLightDrawable> LightDrawable for T {
+    fn draw(&self, context: &mut LightContext) {
+        LegacyLightDrawable::draw(
+            self,
+            context.program,
+            context.transform_matrix,
+            context.framebuffer,
+            context.height,
+            context.albedo,
+            context.material,
+            context.shadow_strength,
+        );
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        LegacyLightDrawable::try_load_shaders(self, program);
+    }
+
+    fn get_transform(&self) -> [[f32; 4]; 4] {
+        LegacyLightDrawable::get_transform(self)
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        LegacyLightDrawable::get_light_layers(self)
+    }
+
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        cutoff: f32,
+    ) -> Option<LightBounds> {
+        LegacyLightDrawable::get_influence_bounds(self, matrix_transform, resolution, cutoff)
+    }
 }
 
 /// A point light source
@@ -60,6 +514,12 @@ pub struct PointLight {
     color: [f32; 3],
     intensity: f32,
     falloff: f32,
+    falloff_model: FalloffModel,
+    range: Option<f32>,
+    source_radius: f32,
+    cookie: Option<Cookie>,
+    shadows: ShadowSettings,
+    light_layers: u8,
 }
 
 impl PointLight {
@@ -70,6 +530,12 @@ impl PointLight {
             color,
             intensity,
             falloff,
+            falloff_model: FalloffModel::InverseSquare,
+            range: None,
+            source_radius: 0.0,
+            cookie: None,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
         }
     }
 
@@ -94,32 +560,83 @@ impl PointLight {
         self.intensity = intensity;
     }
 
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
     /// Set the falloff of the light
     pub fn set_falloff(&mut self, falloff: f32) {
         self.falloff = falloff;
     }
+
+    /// Set the curve the light fades out with
+    pub fn set_falloff_model(&mut self, falloff_model: FalloffModel) {
+        self.falloff_model = falloff_model;
+    }
+
+    /// Set where the light reaches exactly zero in pixels, None lets it fade out forever
+    pub fn set_range(&mut self, range: Option<f32>) {
+        self.range = range;
+    }
+
+    /// Set the radius of the light in pixels
+    /// bigger lights cast softer shadows, 0.0 gives hard shadows
+    pub fn set_source_radius(&mut self, source_radius: f32) {
+        self.source_radius = source_radius;
+    }
+
+    /// Set the cookie the light projects, None for plain light
+    pub fn set_cookie(&mut self, cookie: Option<Cookie>) {
+        self.cookie = cookie;
+    }
+}
+
+impl AnimatableLight for PointLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some(self.position),
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.range,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            self.position = position;
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+        if self.range.is_some() {
+            self.range = state.radius;
+        }
+    }
 }
 
 impl LightDrawable for PointLight {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut SimpleFrameBuffer,
-        height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        reflection_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    ) {
-        draw_point_light(
-            albedo_uniform,
-            height_uniform,
-            shadow_strength_uniform,
-            albedo_framebuffer,
-            program,
-            &self,
-            matrix_transform,
-        )
+    fn draw(&self, context: &mut LightContext) {
+        draw_point_light(context, self)
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
@@ -127,7 +644,7 @@ impl LightDrawable for PointLight {
             let shader = glium::Program::from_source(
                 &program.display,
                 POINT_LIGHT_VERTEX_SHADER_SRC,
-                POINT_LIGHT_FRAGMENT_SHADER_SRC,
+                &preprocess_shader(POINT_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
                 None,
             )
             .unwrap();
@@ -136,6 +653,10 @@ impl LightDrawable for PointLight {
         }
     }
 
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
     fn get_transform(&self) -> [[f32; 4]; 4] {
         [
             [1.0, 0.0, 0.0, 0.0],
@@ -144,16 +665,50 @@ impl LightDrawable for PointLight {
             [self.position[0], self.position[1], self.position[2], 0.0],
         ]
     }
+
+    fn as_batched_point_light(&self) -> Option<&PointLight> {
+        // the batched pass has no cookies or gradients
+        if self.cookie.is_some() || matches!(self.falloff_model, FalloffModel::Gradient(_)) {
+            None
+        } else {
+            Some(self)
+        }
+    }
+
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        cutoff: f32,
+    ) -> Option<LightBounds> {
+        let radius = self
+            .falloff_model
+            .reach(self.intensity, self.falloff, self.range, cutoff)?;
+
+        // the same position as draw_point_light, but in pixels
+        let center = [
+            (matrix_transform[3][0] + 1.0) * 0.5 * resolution[0] as f32,
+            (matrix_transform[3][1] + 1.0) * 0.5 * resolution[1] as f32,
+        ];
+
+        Some(LightBounds::around(center, radius))
+    }
 }
 
 /// An area light source
+/// the shadows are softened over the width and height of the light
+/// this is always an axis aligned rectangle, for a rotated strip of light use LineLight
 pub struct AreaLight {
     position: [f32; 3],
     color: [f32; 3],
     intensity: f32,
     falloff: f32,
+    falloff_model: FalloffModel,
+    range: Option<f32>,
     width: f32,
     height: f32,
+    shadows: ShadowSettings,
+    light_layers: u8,
 }
 
 impl AreaLight {
@@ -171,8 +726,12 @@ impl AreaLight {
             color,
             intensity,
             falloff,
+            falloff_model: FalloffModel::InverseSquare,
+            range: None,
             width,
             height,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
         }
     }
 
@@ -196,11 +755,46 @@ impl AreaLight {
         self.intensity = intensity;
     }
 
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
     /// Set the falloff of the light
     pub fn set_falloff(&mut self, falloff: f32) {
         self.falloff = falloff;
     }
 
+    /// Set the curve the light fades out with
+    pub fn set_falloff_model(&mut self, falloff_model: FalloffModel) {
+        self.falloff_model = falloff_model;
+    }
+
+    /// Set where the light reaches exactly zero in pixels, None lets it fade out forever
+    pub fn set_range(&mut self, range: Option<f32>) {
+        self.range = range;
+    }
+
     /// Set the width of the light
     pub fn set_width(&mut self, width: f32) {
         self.width = width;
@@ -212,26 +806,31 @@ impl AreaLight {
     }
 }
 
+impl AnimatableLight for AreaLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some(self.position),
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.range,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            self.position = position;
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+        if self.range.is_some() {
+            self.range = state.radius;
+        }
+    }
+}
+
 impl LightDrawable for AreaLight {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut SimpleFrameBuffer,
-        height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        reflection_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    ) {
-        draw_area_light(
-            program,
-            albedo_framebuffer,
-            albedo_uniform,
-            height_uniform,
-            shadow_strength_uniform,
-            &self,
-            matrix_transform,
-        )
+    fn draw(&self, context: &mut LightContext) {
+        draw_area_light(context, self)
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
@@ -239,7 +838,7 @@ impl LightDrawable for AreaLight {
             let shader = glium::Program::from_source(
                 &program.display,
                 RECTANGLE_LIGHT_VERTEX_SHADER_SRC,
-                RECTANGLE_LIGHT_FRAGMENT_SHADER_SRC,
+                &preprocess_shader(RECTANGLE_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
                 None,
             )
             .unwrap();
@@ -248,6 +847,10 @@ impl LightDrawable for AreaLight {
         }
     }
 
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
     fn get_transform(&self) -> [[f32; 4]; 4] {
         [
             [self.width, 0.0, 0.0, 0.0],
@@ -256,9 +859,43 @@ impl LightDrawable for AreaLight {
             [self.position[0], self.position[1], self.position[2], 1.0],
         ]
     }
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        cutoff: f32,
+    ) -> Option<LightBounds> {
+        let radius = self
+            .falloff_model
+            .reach(self.intensity, self.falloff, self.range, cutoff)?;
+
+        // the same box as draw_area_light, but in pixels
+        let position = to_texture_position(matrix_transform, [0.0, 0.0, 0.0]);
+        let center = [
+            position[0] * resolution[0] as f32,
+            position[1] * resolution[1] as f32,
+        ];
+        let half_size = [
+            self.width * matrix_transform[0][0] * 0.5 * resolution[0] as f32,
+            self.height * matrix_transform[1][1] * 0.5 * resolution[1] as f32,
+        ];
+
+        Some(LightBounds::new(
+            [
+                center[0] - half_size[0] - radius,
+                center[1] - half_size[1] - radius,
+            ],
+            [
+                center[0] + half_size[0] + radius,
+                center[1] + half_size[1] + radius,
+            ],
+        ))
+    }
 }
 
 /// A directional light source with directional and distance falloff
+/// this shines from a position, for parallel rays from far away use SunLight
+/// and for a cone with a hard edge use SpotLight
 pub struct DirectionalLight {
     position: [f32; 3],
     direction: [f32; 3],
@@ -266,6 +903,10 @@ pub struct DirectionalLight {
     intensity: f32,
     angular_falloff: f32,
     distance_falloff: f32,
+    source_radius: f32,
+    cookie: Option<Cookie>,
+    shadows: ShadowSettings,
+    light_layers: u8,
 }
 
 impl Default for DirectionalLight {
@@ -277,6 +918,10 @@ impl Default for DirectionalLight {
             intensity: 1.0,
             angular_falloff: 0.001,
             distance_falloff: 0.0,
+            source_radius: 0.0,
+            cookie: None,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
         }
     }
 }
@@ -298,6 +943,10 @@ impl DirectionalLight {
             intensity,
             angular_falloff,
             distance_falloff,
+            source_radius: 0.0,
+            cookie: None,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
         }
     }
 
@@ -324,6 +973,31 @@ impl DirectionalLight {
         self.intensity = intensity;
     }
 
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
     /// Set the angular falloff of the light
     /// 0.0 is no falloff, 1.0 is full falloff
     pub fn set_angular_falloff(&mut self, angular_falloff: f32) {
@@ -335,28 +1009,41 @@ impl DirectionalLight {
     pub fn set_distance_falloff(&mut self, distance_falloff: f32) {
         self.distance_falloff = distance_falloff;
     }
+
+    /// Set the radius of the light in pixels
+    /// bigger lights cast softer shadows, 0.0 gives hard shadows
+    pub fn set_source_radius(&mut self, source_radius: f32) {
+        self.source_radius = source_radius;
+    }
+
+    /// Set the cookie the light projects, None for plain light
+    pub fn set_cookie(&mut self, cookie: Option<Cookie>) {
+        self.cookie = cookie;
+    }
+}
+
+impl AnimatableLight for DirectionalLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some(self.position),
+            color: self.color,
+            intensity: self.intensity,
+            radius: None,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            self.position = position;
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+    }
 }
 
 impl LightDrawable for DirectionalLight {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut SimpleFrameBuffer,
-        height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        reflection_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-        shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    ) {
-        draw_directional_light(
-            program,
-            albedo_framebuffer,
-            albedo_uniform,
-            height_uniform,
-            shadow_strength_uniform,
-            &self,
-            matrix_transform,
-        )
+    fn draw(&self, context: &mut LightContext) {
+        draw_directional_light(context, self)
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
@@ -364,7 +1051,7 @@ impl LightDrawable for DirectionalLight {
             let shader = glium::Program::from_source(
                 &program.display,
                 DIRECTIONAL_LIGHT_VERTEX_SHADER_SRC,
-                DIRECTIONAL_LIGHT_FRAGMENT_SHADER_SRC,
+                &preprocess_shader(DIRECTIONAL_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
                 None,
             )
             .unwrap();
@@ -373,6 +1060,10 @@ impl LightDrawable for DirectionalLight {
         }
     }
 
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
     fn get_transform(&self) -> [[f32; 4]; 4] {
         [
             [1.0, 0.0, 0.0, 0.0],
@@ -383,151 +1074,1310 @@ impl LightDrawable for DirectionalLight {
     }
 }
 
-/// draw the point light
-pub(crate) fn draw_point_light(
-    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    heightmap: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    framebuffer: &mut SimpleFrameBuffer,
-    program: &LumenpyxProgram,
-    light: &PointLight,
-    matrix_transform: [[f32; 4]; 4],
-) {
-    let display = &program.display;
-    let indices = &program.indices;
-    let shader = &program.get_shader("point_light_shader").unwrap();
-
-    let shape = FULL_SCREEN_QUAD;
+/// A spot light, like a flashlight or a streetlamp
+/// the light is full strength inside the inner cone and smoothly fades out by the outer cone,
+/// it fades to nothing at the range
+pub struct SpotLight {
+    position: [f32; 3],
+    direction: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    inner_angle: f32,
+    outer_angle: f32,
+    range: f32,
+    source_radius: f32,
+    cookie: Option<Cookie>,
+    shadows: ShadowSettings,
+    light_layers: u8,
+}
 
-    // the magic numbers are to transform the light position from -1.0 to 1.0 to 0.0 to 1.0
-    let light_pos = [
-        ((matrix_transform[3][0]) + 1.0) * 0.5,
-        ((matrix_transform[3][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
-    ];
+impl Default for SpotLight {
+    fn default() -> Self {
+        SpotLight {
+            position: [0.0, 0.0, 0.0],
+            direction: [1.0, 0.0, 0.0],
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+            range: 64.0,
+            source_radius: 0.0,
+            cookie: None,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        }
+    }
+}
+
+impl SpotLight {
+    /// Create a new spot light
+    /// direction is the way the light points, x and y are along the screen and z is straight up,
+    /// with a height of 1.0 being as tall as the screen is wide
+    /// the cone angles are measured from the direction to the edge of the cone in radians
+    /// range is how far the light reaches in pixels
+    pub fn new(
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        range: f32,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            direction,
+            color,
+            intensity,
+            inner_angle,
+            outer_angle,
+            range,
+            source_radius: 0.0,
+            cookie: None,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        }
+    }
+
+    /// Set the position of the light
+    pub fn set_position(&mut self, x: f32, y: f32, z: f32) {
+        self.position = [x, y, z];
+    }
+
+    /// Get the position of the light
+    pub fn get_position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Set the direction the light points in
+    pub fn set_direction(&mut self, x: f32, y: f32, z: f32) {
+        self.direction = [x, y, z];
+    }
+
+    /// Get the direction the light points in
+    pub fn get_direction(&self) -> [f32; 3] {
+        self.direction
+    }
+
+    /// Set the color of the light in 0.0 - 1.0 range
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+    }
+
+    /// Set the intensity of the light
+    /// If the intensity is above 1.0, it can result in overexposure
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set the cone angles in radians, inside the inner angle the light is full strength
+    /// and it smoothly fades out by the outer angle
+    pub fn set_cone_angles(&mut self, inner_angle: f32, outer_angle: f32) {
+        self.inner_angle = inner_angle;
+        self.outer_angle = outer_angle;
+    }
+
+    /// Set how far the light reaches in pixels
+    pub fn set_range(&mut self, range: f32) {
+        self.range = range;
+    }
+
+    /// Set the radius of the light in pixels
+    /// bigger lights cast softer shadows, 0.0 gives hard shadows
+    pub fn set_source_radius(&mut self, source_radius: f32) {
+        self.source_radius = source_radius;
+    }
+
+    /// Set the cookie the light projects, None for plain light
+    pub fn set_cookie(&mut self, cookie: Option<Cookie>) {
+        self.cookie = cookie;
+    }
+}
+
+impl AnimatableLight for SpotLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some(self.position),
+            color: self.color,
+            intensity: self.intensity,
+            radius: Some(self.range),
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            self.position = position;
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+        if let Some(radius) = state.radius {
+            self.range = radius;
+        }
+    }
+}
+
+impl LightDrawable for SpotLight {
+    fn draw(&self, context: &mut LightContext) {
+        draw_spot_light(context, self)
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        if program.get_shader("spot_light_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                SPOT_LIGHT_VERTEX_SHADER_SRC,
+                &preprocess_shader(SPOT_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, "spot_light_shader");
+        }
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_transform(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [self.position[0], self.position[1], self.position[2], 0.0],
+        ]
+    }
+
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        _cutoff: f32,
+    ) -> Option<LightBounds> {
+        // the same position as draw_spot_light, but in pixels
+        let center = [
+            (matrix_transform[3][0] + 1.0) * 0.5 * resolution[0] as f32,
+            (matrix_transform[3][1] + 1.0) * 0.5 * resolution[1] as f32,
+        ];
+
+        // the light is exactly zero past the range, so the cutoff isn't needed
+        Some(LightBounds::around(center, self.range))
+    }
+}
+
+/// A light along a line, like a neon tube, a lava crack or a laser beam
+/// every pixel is lit from the closest point on the line, and the light is full brightness
+/// within the radius of the line, making it a capsule
+pub struct LineLight {
+    start: [f32; 3],
+    end: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    falloff: f32,
+    falloff_model: FalloffModel,
+    range: Option<f32>,
+    radius: f32,
+    shadows: ShadowSettings,
+    light_layers: u8,
+}
+
+impl LineLight {
+    /// Create a new line light between two points
+    /// radius is how thick the line is in pixels
+    pub fn new(
+        start: [f32; 3],
+        end: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        falloff: f32,
+        radius: f32,
+    ) -> LineLight {
+        LineLight {
+            start,
+            end,
+            color,
+            intensity,
+            falloff,
+            falloff_model: FalloffModel::InverseSquare,
+            range: None,
+            radius,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        }
+    }
+
+    /// Set the two ends of the line
+    pub fn set_points(&mut self, start: [f32; 3], end: [f32; 3]) {
+        self.start = start;
+        self.end = end;
+    }
+
+    /// Get the two ends of the line
+    pub fn get_points(&self) -> ([f32; 3], [f32; 3]) {
+        (self.start, self.end)
+    }
 
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+    /// Set the color of the light in 0.0 - 1.0 range
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+    }
+
+    /// Set the intensity of the light
+    /// If the intensity is above 1.0, it can result in overexposure
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set the falloff of the light
+    pub fn set_falloff(&mut self, falloff: f32) {
+        self.falloff = falloff;
+    }
+
+    /// Set the curve the light fades out with
+    pub fn set_falloff_model(&mut self, falloff_model: FalloffModel) {
+        self.falloff_model = falloff_model;
+    }
+
+    /// Set where the light reaches exactly zero in pixels, None lets it fade out forever
+    pub fn set_range(&mut self, range: Option<f32>) {
+        self.range = range;
+    }
+
+    /// Set how thick the line is in pixels
+    /// thicker lines also cast softer shadows
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    /// the ends of the line in 0.0 to 1.0 screen space, moved with the camera like the transform
+    fn get_screen_points(&self, matrix_transform: [[f32; 4]; 4]) -> ([f32; 3], [f32; 3]) {
+        // the transform is at the start of the line, so the end is moved by the same amount
+        let offset = [
+            matrix_transform[3][0] - self.start[0],
+            matrix_transform[3][1] - self.start[1],
+        ];
+        let start = [
+            ((matrix_transform[3][0]) + 1.0) * 0.5,
+            ((matrix_transform[3][1]) + 1.0) * 0.5,
+            self.start[2] * matrix_transform[2][2],
+        ];
+        let end = [
+            ((self.end[0] + offset[0]) + 1.0) * 0.5,
+            ((self.end[1] + offset[1]) + 1.0) * 0.5,
+            self.end[2] * matrix_transform[2][2],
+        ];
+        (start, end)
+    }
+}
+
+impl AnimatableLight for LineLight {
+    // the position of a line light is its middle, moving it moves both ends
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some([
+                (self.start[0] + self.end[0]) * 0.5,
+                (self.start[1] + self.end[1]) * 0.5,
+                (self.start[2] + self.end[2]) * 0.5,
+            ]),
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.range,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            for ((position, start), end) in position
+                .iter()
+                .zip(self.start.iter_mut())
+                .zip(self.end.iter_mut())
+            {
+                let offset = position - (*start + *end) * 0.5;
+                *start += offset;
+                *end += offset;
+            }
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+        if self.range.is_some() {
+            self.range = state.radius;
+        }
+    }
+}
+
+impl LightDrawable for LineLight {
+    fn draw(&self, context: &mut LightContext) {
+        draw_line_light(context, self)
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        if program.get_shader("line_light_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                LINE_LIGHT_VERTEX_SHADER_SRC,
+                &preprocess_shader(LINE_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, "line_light_shader");
+        }
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_transform(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [self.start[0], self.start[1], self.start[2], 0.0],
+        ]
+    }
+
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        cutoff: f32,
+    ) -> Option<LightBounds> {
+        // the same as a point light, plus the thickness of the line
+        let radius = self
+            .falloff_model
+            .reach(self.intensity, self.falloff, self.range, cutoff)?
+            + self.radius;
+
+        let (start, end) = self.get_screen_points(matrix_transform);
+        let to_pixels = |point: [f32; 3]| {
+            [
+                point[0] * resolution[0] as f32,
+                point[1] * resolution[1] as f32,
+            ]
+        };
+
+        Some(
+            LightBounds::around(to_pixels(start), radius)
+                .union(LightBounds::around(to_pixels(end), radius)),
+        )
+    }
+}
+
+/// An infinitely far away light like the sun or the moon
+/// all of its rays are parallel, so every shadow points the same way and it doesn't fall off with distance
+/// the shadows are traced all the way to the edge of the screen,
+/// with a height of 1.0 being as tall as the screen is wide
+pub struct SunLight {
+    azimuth: f32,
+    elevation: f32,
+    color: [f32; 3],
+    intensity: f32,
+    angular_radius: f32,
+    shadows: ShadowSettings,
+    light_layers: u8,
+}
+
+impl Default for SunLight {
+    fn default() -> Self {
+        SunLight {
+            azimuth: 0.0,
+            elevation: std::f32::consts::FRAC_PI_4,
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            angular_radius: 0.0,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        }
+    }
+}
+
+impl SunLight {
+    /// Create a new sun light
+    /// azimuth is the direction the light comes from in radians, 0.0 is from the right and it goes counter clockwise
+    /// elevation is how high the sun is above the ground in radians, PI / 2 is straight above
+    pub fn new(azimuth: f32, elevation: f32, color: [f32; 3], intensity: f32) -> SunLight {
+        SunLight {
+            azimuth,
+            elevation,
+            color,
+            intensity,
+            angular_radius: 0.0,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        }
+    }
+
+    /// Set the direction the light comes from in radians, 0.0 is from the right and it goes counter clockwise
+    pub fn set_azimuth(&mut self, azimuth: f32) {
+        self.azimuth = azimuth;
+    }
+
+    /// Get the direction the light comes from in radians
+    pub fn get_azimuth(&self) -> f32 {
+        self.azimuth
+    }
+
+    /// Set how high the sun is above the ground in radians, PI / 2 is straight above
+    /// lower suns cast longer shadows
+    pub fn set_elevation(&mut self, elevation: f32) {
+        self.elevation = elevation;
+    }
+
+    /// Get how high the sun is above the ground in radians
+    pub fn get_elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    /// Set the color of the light in 0.0 - 1.0 range
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+    }
+
+    /// Set the intensity of the light
+    /// If the intensity is above 1.0, it can result in overexposure
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how big the sun looks in radians
+    /// bigger suns cast softer shadows, 0.0 gives hard shadows
+    pub fn set_angular_radius(&mut self, angular_radius: f32) {
+        self.angular_radius = angular_radius;
+    }
+
+    /// Get the direction the light comes from as a vector,
+    /// x and y are along the screen and z is straight up
+    pub fn get_direction(&self) -> [f32; 3] {
+        [
+            self.azimuth.cos() * self.elevation.cos(),
+            self.azimuth.sin() * self.elevation.cos(),
+            self.elevation.sin(),
+        ]
+    }
+}
+
+impl AnimatableLight for SunLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: None,
+            color: self.color,
+            intensity: self.intensity,
+            radius: None,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        self.color = state.color;
+        self.intensity = state.intensity;
+    }
+}
+
+impl LightDrawable for SunLight {
+    fn draw(&self, context: &mut LightContext) {
+        draw_sun_light(context, self)
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        if program.get_shader("sun_light_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                SUN_LIGHT_VERTEX_SHADER_SRC,
+                &preprocess_shader(SUN_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, "sun_light_shader");
+        }
+    }
+
+    /// the sun has no position, so the camera doesn't move it
+    fn get_transform(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+}
+
+/// A custom light that only says how much light reaches a pixel, without shadows.
+/// The shadows, light layers, culling and blending are done the same way as the built-in lights.
+/// The radiance is the glsl of a function
+/// `vec3 light_radiance(vec2 coords, vec3 pixel, vec3 light)`
+/// where pixel and light are in pixels with the height in the z component.
+/// It can read the uniforms light_color, light_intensity and light_params (set_params)
+/// and #include the snippets listed in shaders::preprocess_shader
+/// ```ignore
+/// const RADIANCE: &str = "
+/// vec3 light_radiance(vec2 coords, vec3 pixel, vec3 light) {
+///     // light_params.x is the radius of the ring and light_params.y is its width, in pixels
+///     float ring = 1.0 - abs(distance(pixel.xy, light.xy) - light_params.x) / light_params.y;
+///     return light_color * light_intensity * max(ring, 0.0);
+/// }
+/// ";
+///
+/// let mut light =
+///     ShadowedLight::new("ring", RADIANCE, [0.0, 0.0, 1.0], [1.0, 1.0, 1.0], 1.0).unwrap();
+/// light.set_params([40.0, 10.0, 0.0, 0.0]);
+/// light.set_range(Some(50.0));
+/// ```
+#[derive(Clone)]
+pub struct ShadowedLight {
+    shader_name: String,
+    fragment_shader: String,
+    position: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    params: [f32; 4],
+    range: Option<f32>,
+    source_radius: f32,
+    shadows: ShadowSettings,
+    light_layers: u8,
+}
+
+impl ShadowedLight {
+    /// Create a new shadowed light,
+    /// the shader is shared by every light with the same name so only reuse a name for the same radiance.
+    /// This fails if the radiance includes a snippet that doesn't exist
+    pub fn new(
+        name: &str,
+        radiance: &str,
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    ) -> Result<ShadowedLight, UnknownShaderInclude> {
+        // the radiance goes after the template, which declares it before main
+        let fragment_shader = preprocess_shader(&format!(
+            "{}\n{}",
+            SHADOWED_LIGHT_FRAGMENT_SHADER_SRC, radiance
+        ))?;
+
+        Ok(ShadowedLight {
+            shader_name: format!("shadowed_light_shader_{}", name),
+            fragment_shader,
+            position,
+            color,
+            intensity,
+            params: [0.0; 4],
+            range: None,
+            source_radius: 0.0,
+            shadows: ShadowSettings::default(),
+            light_layers: ALL_LIGHT_LAYERS,
+        })
+    }
+
+    /// Set the position of the light
+    pub fn set_position(&mut self, x: f32, y: f32, z: f32) {
+        self.position = [x, y, z];
+    }
+
+    /// Get the position of the light
+    pub fn get_position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Set the color of the light in 0.0 - 1.0 range
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+    }
+
+    /// Set the intensity of the light
+    /// If the intensity is above 1.0, it can result in overexposure
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Set the light_params uniform of the radiance function
+    pub fn set_params(&mut self, params: [f32; 4]) {
+        self.params = params;
+    }
+
+    /// Get the light_params uniform of the radiance function
+    pub fn get_params(&self) -> [f32; 4] {
+        self.params
+    }
+
+    /// Set how far the light reaches in pixels, it is only drawn inside this range.
+    /// None draws it over the whole screen, set it whenever the radiance reaches zero
+    pub fn set_range(&mut self, range: Option<f32>) {
+        self.range = range;
+    }
+
+    /// Set the radius of the light in pixels
+    /// bigger lights cast softer shadows, 0.0 gives hard shadows
+    pub fn set_source_radius(&mut self, source_radius: f32) {
+        self.source_radius = source_radius;
+    }
+
+    /// Set if the light casts shadows, lights that don't are cheaper to draw
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.shadows.cast_shadows = cast_shadows;
+    }
+
+    /// Set how dark the shadows of the light are in 0.0 - 1.0 range
+    pub fn set_shadow_opacity(&mut self, opacity: f32) {
+        self.shadows.opacity = opacity;
+    }
+
+    /// Set the color the shadows of the light are tinted, None is black
+    pub fn set_shadow_color(&mut self, color: Option<[f32; 3]>) {
+        self.shadows.color = color;
+    }
+
+    /// Get how the light casts shadows
+    pub fn get_shadow_settings(&self) -> ShadowSettings {
+        self.shadows
+    }
+
+    /// Set which light layers the light shines on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+}
+
+impl AnimatableLight for ShadowedLight {
+    fn get_light_state(&self) -> LightState {
+        LightState {
+            position: Some(self.position),
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.range,
+        }
+    }
+
+    fn set_light_state(&mut self, state: &LightState) {
+        if let Some(position) = state.position {
+            self.position = position;
+        }
+        self.color = state.color;
+        self.intensity = state.intensity;
+        if self.range.is_some() {
+            self.range = state.radius;
+        }
+    }
+}
+
+impl LightDrawable for ShadowedLight {
+    fn draw(&self, context: &mut LightContext) {
+        draw_shadowed_light(context, self)
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        if program.get_shader(&self.shader_name).is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                SHADOWED_LIGHT_VERTEX_SHADER_SRC,
+                &self.fragment_shader,
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, &self.shader_name);
+        }
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_transform(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [self.position[0], self.position[1], self.position[2], 0.0],
+        ]
+    }
+
+    fn get_influence_bounds(
+        &self,
+        matrix_transform: [[f32; 4]; 4],
+        resolution: [u32; 2],
+        _cutoff: f32,
+    ) -> Option<LightBounds> {
+        let range = self.range?;
+        let position = to_texture_position(matrix_transform, [0.0, 0.0, 0.0]);
+        let center = [
+            position[0] * resolution[0] as f32,
+            position[1] * resolution[1] as f32,
+        ];
+
+        Some(LightBounds::around(center, range))
+    }
+}
+
+/// draw the point light
+pub(crate) fn draw_point_light(context: &mut LightContext, light: &PointLight) {
+    let program = context.program;
+    let shader = &program.get_shader("point_light_shader").unwrap();
+
+    let light_pos = context.light_position;
+
+    // lights without a cookie project a blank one
+    let cookie = light.cookie.unwrap_or(Cookie::BLANK);
+
+    // lights without a gradient falloff get a blank one
+    let falloff_gradient = match light.falloff_model {
+        FalloffModel::Gradient(texture) => program.get_light_texture(texture),
+        _ => program.get_blank_texture(),
+    };
 
     let uniforms = &uniform! {
-        heightmap: heightmap,
-        albedomap: albedo_uniform,
-        shadow_strength_map: shadow_strength_uniform,
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
         light_pos: light_pos,
         light_color: light.color,
         light_intensity: light.intensity,
         light_falloff: light.falloff,
+        light_range: light.range.unwrap_or(0.0),
+        falloff_model: light.falloff_model.shader_index(),
+        falloff_gradient: glium::uniforms::Sampler(falloff_gradient, GRADIENT_BEHAVIOR),
+        light_source_radius: light.source_radius,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+        cookie_map: glium::uniforms::Sampler(program.get_light_texture(cookie.texture), COOKIE_BEHAVIOR),
+        cookie_rotation: cookie.rotation,
+        cookie_scale: cookie.scale,
     };
 
-    framebuffer
-        .draw(
-            &vertex_buffer,
-            indices,
-            &shader,
-            uniforms,
-            &glium::DrawParameters {
-                blend: DEFAULT_LIGHT_BLENDING,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-}
-
-fn draw_area_light(
-    program: &LumenpyxProgram,
-    framebuffer: &mut SimpleFrameBuffer,
-    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    light: &AreaLight,
-    matrix_transform: [[f32; 4]; 4],
-) {
-    let display = &program.display;
+    context.draw_full_screen(shader, uniforms);
+}
+
+/// a point light waiting to be drawn in a batch, with its transform and the bounds it reaches on screen
+pub(crate) type BatchedPointLight<'a> = (&'a PointLight, [[f32; 4]; 4], Option<LightBounds>);
+
+/// the texture the batched point light shader reads its lights from,
+/// one column per light and one row per kind of data
+pub(crate) fn new_batched_light_data(
+    display: &glium::Display<glium::glutin::surface::WindowSurface>,
+) -> glium::texture::Texture2d {
+    glium::texture::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::F32F32F32F32,
+        glium::texture::MipmapsOption::NoMipmap,
+        MAX_BATCHED_POINT_LIGHTS_PER_PASS as u32,
+        4,
+    )
+    .unwrap()
+}
+
+/// draw many point lights in as few full screen passes as possible
+/// the lights are uploaded to a data texture, so there is one draw per
+/// MAX_BATCHED_POINT_LIGHTS_PER_PASS lights instead of one per light
+/// every pass is scissored to the bounds of the lights in it, a light with no bounds covers the whole screen
+/// returns how many passes it took
+pub(crate) fn draw_batched_point_lights(
+    context: &mut LightContext,
+    lights: &[BatchedPointLight],
+) -> usize {
+    let program = context.program;
     let indices = &program.indices;
-    let shader = &program.get_shader("rectangle_light_shader").unwrap();
+    let shader = program.get_shader("batched_point_light_shader").unwrap();
 
-    let shape = FULL_SCREEN_QUAD;
+    let vertex_buffer = program.get_full_screen_quad();
 
-    let light_pos = [
-        ((light.position[0] * matrix_transform[0][0]) + 1.0) * 0.5,
-        ((light.position[1] * matrix_transform[1][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
-    ];
-    let light_width = light.width * matrix_transform[0][0];
-    let light_height = light.height * matrix_transform[1][1];
+    // gradient lights aren't batched, but the sampler still needs something bound
+    let blank_gradient = program.get_blank_texture();
+
+    let mut passes = 0;
+    for chunk in lights.chunks(MAX_BATCHED_POINT_LIGHTS_PER_PASS) {
+        let mut position_row = Vec::with_capacity(chunk.len());
+        let mut color_row = Vec::with_capacity(chunk.len());
+        let mut radius_row = Vec::with_capacity(chunk.len());
+        let mut shadow_row = Vec::with_capacity(chunk.len());
+        for (light, matrix_transform, _) in chunk {
+            // the same as draw_point_light, from -1.0 to 1.0 to 0.0 to 1.0
+            position_row.push((
+                ((matrix_transform[3][0]) + 1.0) * 0.5,
+                ((matrix_transform[3][1]) + 1.0) * 0.5,
+                light.position[2] * matrix_transform[2][2],
+                light.intensity,
+            ));
+            color_row.push((
+                light.color[0],
+                light.color[1],
+                light.color[2],
+                light.falloff,
+            ));
+            radius_row.push((
+                light.source_radius,
+                light.range.unwrap_or(0.0),
+                light.falloff_model.shader_index() as f32,
+                light.light_layers as f32,
+            ));
+            let shadow_color = light.shadows.shader_color();
+            shadow_row.push((
+                shadow_color[0],
+                shadow_color[1],
+                shadow_color[2],
+                light.shadows.shader_opacity(),
+            ));
+        }
+
+        let scissor = chunk
+            .iter()
+            .map(|(_, _, bounds)| *bounds)
+            .reduce(|total, bounds| match (total, bounds) {
+                (Some(total), Some(bounds)) => Some(total.union(bounds)),
+                _ => None,
+            })
+            .flatten();
 
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+        // only the first light_count columns are read, so the rest can be left over from the last pass
+        let light_data = &program.batched_light_data;
+        light_data.write(
+            glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: chunk.len() as u32,
+                height: 4,
+            },
+            vec![position_row, color_row, radius_row, shadow_row],
+        );
+
+        let uniforms = &uniform! {
+            heightmap: context.height,
+            albedomap: context.albedo,
+            shadow_strength_map: context.shadow_strength,
+            materialmap: context.material,
+            lightdata: glium::uniforms::Sampler(light_data, crate::DEFAULT_BEHAVIOR),
+            light_count: chunk.len() as i32,
+            falloff_gradient: glium::uniforms::Sampler(blank_gradient, GRADIENT_BEHAVIOR),
+            shadow_samples: program.render_settings.shadow_samples as i32,
+        };
+
+        context
+            .framebuffer
+            .draw(
+                vertex_buffer,
+                indices,
+                shader,
+                uniforms,
+                &glium::DrawParameters {
+                    blend: DEFAULT_LIGHT_BLENDING,
+                    scissor: scissor.map(|bounds| bounds.to_scissor()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        passes += 1;
+    }
+
+    passes
+}
+
+fn draw_area_light(context: &mut LightContext, light: &AreaLight) {
+    let program = context.program;
+    let shader = &program.get_shader("rectangle_light_shader").unwrap();
+
+    let light_pos = context.light_position;
+    let light_width = light.width * context.transform_matrix[0][0];
+    let light_height = light.height * context.transform_matrix[1][1];
+
+    // lights without a gradient falloff get a blank one
+    let falloff_gradient = match light.falloff_model {
+        FalloffModel::Gradient(texture) => program.get_light_texture(texture),
+        _ => program.get_blank_texture(),
+    };
 
     let uniforms = &uniform! {
-        heightmap: height_uniform,
-        albedomap: albedo_uniform,
-        shadow_strength_map: shadow_strength_uniform,
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
         light_pos: light_pos,
         light_color: light.color,
         light_intensity: light.intensity,
         light_falloff: light.falloff,
+        light_range: light.range.unwrap_or(0.0),
+        falloff_model: light.falloff_model.shader_index(),
+        falloff_gradient: glium::uniforms::Sampler(falloff_gradient, GRADIENT_BEHAVIOR),
         width: light_width,
         height: light_height,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
     };
 
-    framebuffer
-        .draw(
-            &vertex_buffer,
-            indices,
-            &shader,
-            uniforms,
-            &glium::DrawParameters {
-                blend: DEFAULT_LIGHT_BLENDING,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-}
-
-fn draw_directional_light(
-    program: &LumenpyxProgram,
-    framebuffer: &mut SimpleFrameBuffer,
-    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    light: &DirectionalLight,
-    matrix_transform: [[f32; 4]; 4],
-) {
-    let display = &program.display;
-    let indices = &program.indices;
-    let shader = &program.get_shader("directional_light_shader").unwrap();
+    context.draw_full_screen(shader, uniforms);
+}
 
-    let shape = FULL_SCREEN_QUAD;
+fn draw_directional_light(context: &mut LightContext, light: &DirectionalLight) {
+    let program = context.program;
+    let shader = &program.get_shader("directional_light_shader").unwrap();
 
     let light_pos = [
-        ((light.position[0] * matrix_transform[0][0]) + 1.0) * 0.5,
-        ((light.position[1] * matrix_transform[1][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
+        ((light.position[0] * context.transform_matrix[0][0]) + 1.0) * 0.5,
+        ((light.position[1] * context.transform_matrix[1][1]) + 1.0) * 0.5,
+        light.position[2] * context.transform_matrix[2][2],
     ];
 
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+    // lights without a cookie project a blank one
+    let cookie = light.cookie.unwrap_or(Cookie::BLANK);
 
     let uniforms = &uniform! {
-        heightmap: height_uniform,
-        albedomap: albedo_uniform,
-        shadow_strength_map: shadow_strength_uniform,
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
         light_pos: light_pos,
         light_color: light.color,
         light_intensity: light.intensity,
         light_distance_falloff: light.distance_falloff,
         light_angular_falloff: light.angular_falloff,
         light_direction: light.direction,
+        light_source_radius: light.source_radius,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+        cookie_map: glium::uniforms::Sampler(program.get_light_texture(cookie.texture), COOKIE_BEHAVIOR),
+        cookie_rotation: cookie.rotation,
+        cookie_scale: cookie.scale,
     };
 
-    framebuffer
-        .draw(
-            &vertex_buffer,
-            indices,
-            &shader,
-            uniforms,
-            &glium::DrawParameters {
-                blend: DEFAULT_LIGHT_BLENDING,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+    context.draw_full_screen(shader, uniforms);
+}
+
+fn draw_sun_light(context: &mut LightContext, light: &SunLight) {
+    let program = context.program;
+    let shader = &program.get_shader("sun_light_shader").unwrap();
+
+    let uniforms = &uniform! {
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
+        light_direction: light.get_direction(),
+        light_color: light.color,
+        light_intensity: light.intensity,
+        light_angular_radius: light.angular_radius,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+    };
+
+    context.draw_full_screen(shader, uniforms);
+}
+
+fn draw_spot_light(context: &mut LightContext, light: &SpotLight) {
+    let program = context.program;
+    let shader = &program.get_shader("spot_light_shader").unwrap();
+
+    let light_pos = context.light_position;
+
+    // the inner cone has to be a little smaller than the outer cone for the smooth fade
+    let outer_angle = light.outer_angle.max(0.0);
+    let inner_angle = light.inner_angle.min(outer_angle - 0.0001);
+
+    // lights without a cookie project a blank one
+    let cookie = light.cookie.unwrap_or(Cookie::BLANK);
+
+    let uniforms = &uniform! {
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
+        light_pos: light_pos,
+        light_direction: light.direction,
+        light_color: light.color,
+        light_intensity: light.intensity,
+        cos_inner_angle: inner_angle.cos(),
+        cos_outer_angle: outer_angle.cos(),
+        light_range: light.range,
+        light_source_radius: light.source_radius,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+        cookie_map: glium::uniforms::Sampler(program.get_light_texture(cookie.texture), COOKIE_BEHAVIOR),
+        cookie_rotation: cookie.rotation,
+        cookie_scale: cookie.scale,
+    };
+
+    context.draw_full_screen(shader, uniforms);
+}
+
+fn draw_line_light(context: &mut LightContext, light: &LineLight) {
+    let program = context.program;
+    let shader = &program.get_shader("line_light_shader").unwrap();
+
+    let (line_start, line_end) = light.get_screen_points(context.transform_matrix);
+
+    // lights without a gradient falloff get a blank one
+    let falloff_gradient = match light.falloff_model {
+        FalloffModel::Gradient(texture) => program.get_light_texture(texture),
+        _ => program.get_blank_texture(),
+    };
+
+    let uniforms = &uniform! {
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
+        line_start: line_start,
+        line_end: line_end,
+        line_radius: light.radius,
+        light_color: light.color,
+        light_intensity: light.intensity,
+        light_falloff: light.falloff,
+        light_range: light.range.unwrap_or(0.0),
+        falloff_model: light.falloff_model.shader_index(),
+        falloff_gradient: glium::uniforms::Sampler(falloff_gradient, GRADIENT_BEHAVIOR),
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+    };
+
+    context.draw_full_screen(shader, uniforms);
+}
+
+fn draw_shadowed_light(context: &mut LightContext, light: &ShadowedLight) {
+    let program = context.program;
+    let shader = &program.get_shader(&light.shader_name).unwrap();
+
+    let uniforms = &uniform! {
+        heightmap: context.height,
+        albedomap: context.albedo,
+        shadow_strength_map: context.shadow_strength,
+        materialmap: context.material,
+        light_pos: context.light_position,
+        light_color: light.color,
+        light_intensity: light.intensity,
+        light_params: light.params,
+        light_source_radius: light.source_radius,
+        shadow_samples: program.render_settings.shadow_samples as i32,
+        shadow_opacity: light.shadows.shader_opacity(),
+        shadow_color: light.shadows.shader_color(),
+        light_layer_mask: light.light_layers as i32,
+    };
+
+    context.draw_full_screen(shader, uniforms);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_range_limits_every_falloff_model() {
+        for model in [
+            FalloffModel::InverseSquare,
+            FalloffModel::Linear,
+            FalloffModel::Smoothstep,
+            FalloffModel::Constant,
+        ] {
+            assert_eq!(model.reach(1.0, 0.01, Some(40.0), 0.01), Some(40.0));
+        }
+    }
+
+    #[test]
+    fn inverse_square_reaches_the_cutoff() {
+        let (intensity, falloff, cutoff) = (2.0, 0.1, 0.02);
+
+        let reach = FalloffModel::InverseSquare
+            .reach(intensity, falloff, None, cutoff)
+            .unwrap();
+
+        // the brightness at the reach is exactly the cutoff
+        let brightness = intensity / (1.0 + (reach * falloff).powi(2));
+        assert!((brightness - cutoff).abs() < 1e-5);
+    }
+
+    #[test]
+    fn inverse_square_never_fades_out_without_falloff_or_cutoff() {
+        assert_eq!(
+            FalloffModel::InverseSquare.reach(1.0, 0.0, None, 0.01),
+            None
+        );
+        assert_eq!(FalloffModel::InverseSquare.reach(1.0, 0.1, None, 0.0), None);
+    }
+
+    #[test]
+    fn inverse_square_dimmer_than_the_cutoff_reaches_nothing() {
+        assert_eq!(
+            FalloffModel::InverseSquare.reach(0.005, 0.1, None, 0.01),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn the_other_models_reach_one_over_the_falloff() {
+        for model in [
+            FalloffModel::Linear,
+            FalloffModel::Smoothstep,
+            FalloffModel::Constant,
+        ] {
+            assert_eq!(model.reach(1.0, 0.25, None, 0.01), Some(4.0));
+            assert_eq!(model.reach(1.0, 0.0, None, 0.01), None);
+        }
+    }
+
+    #[test]
+    fn bounds_around_a_point_cover_the_radius() {
+        let bounds = LightBounds::around([10.0, 20.0], 5.0);
+
+        assert_eq!(bounds, LightBounds::new([5.0, 15.0], [15.0, 25.0]));
+    }
+
+    #[test]
+    fn union_covers_both_bounds() {
+        let a = LightBounds::new([0.0, 10.0], [5.0, 20.0]);
+        let b = LightBounds::new([-5.0, 12.0], [3.0, 30.0]);
+
+        assert_eq!(a.union(b), LightBounds::new([-5.0, 10.0], [5.0, 30.0]));
+        assert_eq!(a.union(b), b.union(a));
+    }
+
+    #[test]
+    fn scaled_bounds_scale_both_corners() {
+        let bounds = LightBounds::new([4.0, 8.0], [12.0, 16.0]);
+
+        assert_eq!(bounds.scaled(0.5), LightBounds::new([2.0, 4.0], [6.0, 8.0]));
+    }
+
+    #[test]
+    fn bounds_are_clamped_to_the_screen() {
+        let bounds = LightBounds::new([-10.0, 50.0], [30.0, 150.0]);
+
+        assert_eq!(
+            bounds.clamp_to_screen([100, 100]),
+            Some(LightBounds::new([0.0, 50.0], [30.0, 100.0]))
+        );
+    }
+
+    #[test]
+    fn bounds_off_screen_are_culled() {
+        let resolution = [100, 100];
+
+        // fully to the left, above and touching the right edge
+        assert_eq!(
+            LightBounds::new([-20.0, 0.0], [-1.0, 10.0]).clamp_to_screen(resolution),
+            None
+        );
+        assert_eq!(
+            LightBounds::new([0.0, 120.0], [10.0, 130.0]).clamp_to_screen(resolution),
+            None
+        );
+        assert_eq!(
+            LightBounds::new([100.0, 0.0], [110.0, 10.0]).clamp_to_screen(resolution),
+            None
+        );
+    }
+
+    #[test]
+    fn area_light_bounds_follow_the_camera() {
+        let mut light = AreaLight::new([0.25, 0.0, 0.0], [1.0; 3], 1.0, 0.01, 0.5, 0.5);
+        light.set_range(Some(10.0));
+
+        // the camera is half a screen to the right, so the light is a quarter of a screen to its left
+        let mut matrix_transform = light.get_transform();
+        matrix_transform[3][0] -= 0.5;
+
+        // the center is at 37.5 pixels and the light is 25 pixels wide
+        assert_eq!(
+            light.get_influence_bounds(matrix_transform, [100, 100], 0.01),
+            Some(LightBounds::new([15.0, 27.5], [60.0, 72.5]))
+        );
+    }
+
+    #[test]
+    fn scissor_rounds_outwards() {
+        let scissor = LightBounds::new([1.5, 2.25], [10.1, 20.0]).to_scissor();
+
+        assert_eq!(scissor.left, 1);
+        assert_eq!(scissor.bottom, 2);
+        assert_eq!(scissor.width, 10);
+        assert_eq!(scissor.height, 18);
+    }
 }
@@ -1,11 +1,18 @@
+use crate::drawable_object::BlendMode;
+use crate::drawable_object::DrawContext;
+use crate::drawable_object::DEFAULT_LIGHT_LAYERS;
+use crate::gbuffer::GBuffer;
+use crate::lights::PointLight;
 use crate::load_image;
 use crate::shaders;
+use crate::shaders::preprocess_shader;
 use crate::Drawable;
 use crate::LumenpyxProgram;
 use crate::DEFAULT_BEHAVIOR;
 use glium;
 use glium::framebuffer;
 use glium::framebuffer::SimpleFrameBuffer;
+use glium::glutin::surface::WindowSurface;
 use glium::uniform;
 use glium::Surface;
 
@@ -14,37 +21,33 @@ const GENERATE_CIRCLE_VERTEX_SHADER_SRC: &str =
 const GENERATE_CIRCLE_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/primitives/circle_ahr_shader.frag");
 
-const GENERATE_SPHERE_HEIGHT_VERTEX_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/sphere_height_shader.vert");
-const GENERATE_SPHERE_HEIGHT_FRAGMENT_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/sphere_height_shader.frag");
-
-const GENERATE_SPHERE_NORMAL_VERTEX_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/sphere_normal_shader.vert");
-const GENERATE_SPHERE_NORMAL_FRAGMENT_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/sphere_normal_shader.frag");
-
 const GENERATE_RECTANGLE_VERTEX_SHADER_SRC: &str =
     include_str!("../shaders/primitives/rectangle_ahr_shader.vert");
 const GENERATE_RECTANGLE_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/primitives/rectangle_ahr_shader.frag");
 
-const GENERATE_CYLINDER_HEIGHT_VERTEX_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/cylinder_height.vert");
-const GENERATE_CYLINDER_HEIGHT_FRAGMENT_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/cylinder_height.frag");
-
-const GENERATE_CYLINDER_NORMAL_VERTEX_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/cylinder_normal.vert");
-const GENERATE_CYLINDER_NORMAL_FRAGMENT_SHADER_SRC: &str =
-    include_str!("../shaders/primitives/cylinder_normal.frag");
+const CIRCLE_GBUFFER_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/circle_gbuffer.frag");
+const SPHERE_GBUFFER_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/sphere_gbuffer.frag");
+const RECTANGLE_GBUFFER_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/rectangle_gbuffer.frag");
+const CYLINDER_GBUFFER_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/cylinder_gbuffer.frag");
+const SPRITE_GBUFFER_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/sprite_gbuffer.frag");
 
 pub(crate) const BASE_VERTEX_SHADER_SRC: &str =
     include_str!("../shaders/primitives/sprite_ahr_shader.vert");
 pub(crate) const BASE_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/primitives/sprite_ahr_shader.frag");
 
-use crate::shaders::FULL_SCREEN_QUAD;
+const SPRITE_EMISSIVE_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/sprite_emissive_shader.frag");
+
+const SPRITE_RECIEVE_MASK_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/primitives/sprite_recieve_mask_shader.frag");
+
 use crate::Transform;
 
 /// Draws a circle with the given color and radius.
@@ -56,14 +59,11 @@ pub fn draw_circle(
     program: &LumenpyxProgram,
     framebuffer: &mut SimpleFrameBuffer,
 ) {
-    let display = &program.display;
     let indices = &program.indices;
 
     let shader = program.get_shader("circle_ahr_shader").unwrap();
 
-    let shape = FULL_SCREEN_QUAD;
-
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+    let vertex_buffer = program.get_full_screen_quad();
 
     let uniforms = &uniform! {
         circle_color: color,
@@ -73,7 +73,7 @@ pub fn draw_circle(
 
     framebuffer
         .draw(
-            &vertex_buffer,
+            vertex_buffer,
             indices,
             &shader,
             uniforms,
@@ -83,72 +83,68 @@ pub fn draw_circle(
 }
 
 /// Draws a sphere with the given color and radius.
-/// The sphere is drawn in the framebuffer, this is for custom Drawables.
+/// The sphere is drawn in every channel of the gbuffer, this is for custom Drawables.
 pub fn draw_sphere(
     color: [f32; 4],
     radius: f32,
     matrix_transform: [[f32; 4]; 4],
     program: &LumenpyxProgram,
-    albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    gbuffer: &mut GBuffer,
+    blend_mode: BlendMode,
 ) {
-    let display = &program.display;
-    let indices = &program.indices;
-
-    draw_circle(color, radius, matrix_transform, program, albedo_framebuffer);
+    let shader = program.get_shader("sphere_gbuffer_shader").unwrap();
 
-    {
-        let height_shader = program.get_shader("sphere_height_shader").unwrap();
-
-        let shape = FULL_SCREEN_QUAD;
-
-        let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+    let dimensions = gbuffer.get_dimensions();
+    let uniforms = &uniform! {
+        sphere_color: color,
+        radius_squared: radius.powi(2),
+        matrix: matrix_transform,
+        resolution: [dimensions[0] as f32, dimensions[1] as f32],
+    };
 
-        let uniforms = &uniform! {
-            matrix: matrix_transform,
-            radius_squared: radius.powi(2),
-        };
+    gbuffer.draw_blended(program, shader, uniforms, blend_mode);
+}
 
-        height_framebuffer
-            .draw(
-                &vertex_buffer,
-                indices,
-                &height_shader,
-                uniforms,
-                &Default::default(),
-            )
-            .unwrap();
-    }
+/// a flat circle on the ground in every channel of the gbuffer
+fn draw_circle_gbuffer(
+    color: [f32; 4],
+    radius: f32,
+    matrix_transform: [[f32; 4]; 4],
+    program: &LumenpyxProgram,
+    gbuffer: &mut GBuffer,
+    blend_mode: BlendMode,
+) {
+    let shader = program.get_shader("circle_gbuffer_shader").unwrap();
 
-    {
-        let normal_shader = program.get_shader("sphere_normal_shader").unwrap();
+    let uniforms = &uniform! {
+        circle_color: color,
+        radius_squared: radius.powi(2),
+        matrix: matrix_transform,
+    };
 
-        let shape = FULL_SCREEN_QUAD;
+    gbuffer.draw_blended(program, shader, uniforms, blend_mode);
+}
 
-        let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
-        let resolution = [
-            albedo_framebuffer.get_dimensions().0 as f32,
-            albedo_framebuffer.get_dimensions().1 as f32,
-        ];
-        let radius_squared = radius.powi(2);
+/// a flat rectangle on the ground in every channel of the gbuffer
+fn draw_rectangle_gbuffer(
+    color: [f32; 4],
+    width: f32,
+    height: f32,
+    matrix_transform: [[f32; 4]; 4],
+    program: &LumenpyxProgram,
+    gbuffer: &mut GBuffer,
+    blend_mode: BlendMode,
+) {
+    let shader = program.get_shader("rectangle_gbuffer_shader").unwrap();
 
-        let uniforms = &uniform! {
-            matrix: matrix_transform,
-            radius_squared: radius_squared,
-            resolution: resolution,
-        };
+    let uniforms = &uniform! {
+        rect_color: color,
+        width: width,
+        height_on_screen: height,
+        matrix: matrix_transform,
+    };
 
-        normal_framebuffer
-            .draw(
-                &vertex_buffer,
-                indices,
-                &normal_shader,
-                uniforms,
-                &Default::default(),
-            )
-            .unwrap();
-    }
+    gbuffer.draw_blended(program, shader, uniforms, blend_mode);
 }
 
 fn draw_rectangle(
@@ -159,14 +155,11 @@ fn draw_rectangle(
     program: &LumenpyxProgram,
     framebuffer: &mut SimpleFrameBuffer,
 ) {
-    let display = &program.display;
     let indices = &program.indices;
 
     let shader = program.get_shader("rectangle_ahr_shader").unwrap();
 
-    let shape = FULL_SCREEN_QUAD;
-
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+    let vertex_buffer = program.get_full_screen_quad();
 
     let uniforms = &uniform! {
         rect_color: color,
@@ -177,7 +170,7 @@ fn draw_rectangle(
 
     framebuffer
         .draw(
-            &vertex_buffer,
+            vertex_buffer,
             indices,
             &shader,
             uniforms,
@@ -186,12 +179,36 @@ fn draw_rectangle(
         .unwrap();
 }
 
+/// the color primitives draw into the shadow strength texture, see Drawable::draw_recieve_shadows
+fn recieve_shadows_color(shadow_strength: f32, light_layers: u8) -> [f32; 4] {
+    [
+        shadow_strength,
+        light_layers as f32 / 255.0,
+        1.0,
+        shadow_strength,
+    ]
+}
+
+/// the color primitives draw into the emissive texture, black when they don't glow
+fn emissive_color(color: [f32; 3], intensity: f32) -> [f32; 4] {
+    [
+        color[0] * intensity,
+        color[1] * intensity,
+        color[2] * intensity,
+        1.0,
+    ]
+}
+
 /// A circle primitive.
 pub struct Circle {
     color: [f32; 4],
     radius: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    emissive: [f32; 3],
+    emissive_intensity: f32,
+    light_layers: u8,
+    blend_mode: BlendMode,
 }
 
 impl Circle {
@@ -202,43 +219,67 @@ impl Circle {
             radius,
             transform,
             shadow_strength: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            emissive_intensity: 0.0,
+            light_layers: DEFAULT_LIGHT_LAYERS,
+            blend_mode: BlendMode::Normal,
         }
     }
+
+    /// Make the circle glow with the given color, an intensity of 0.0 turns the glow off.
+    /// The glow is never shadowed
+    pub fn set_emissive(&mut self, color: [f32; 3], intensity: f32) {
+        self.emissive = color;
+        self.emissive_intensity = intensity;
+    }
+
+    /// Set the light layers the circle is on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how the circle is blended with what is beneath it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
 }
 
 impl Drawable for Circle {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
-        draw_circle(
+    fn draw(&self, context: &mut DrawContext) {
+        draw_circle_gbuffer(
             self.color,
             self.radius,
-            matrix_transform,
-            program,
-            albedo_framebuffer,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer,
+            self.blend_mode,
         );
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
-        if program.get_shader("circle_ahr_shader").is_some() {
-            return;
+        if program.get_shader("circle_gbuffer_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                BASE_VERTEX_SHADER_SRC,
+                &preprocess_shader(CIRCLE_GBUFFER_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, "circle_gbuffer_shader");
         }
 
-        let shader = glium::Program::from_source(
-            &program.display,
-            GENERATE_CIRCLE_VERTEX_SHADER_SRC,
-            GENERATE_CIRCLE_FRAGMENT_SHADER_SRC,
-            None,
-        )
-        .unwrap();
+        if program.get_shader("circle_ahr_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                GENERATE_CIRCLE_VERTEX_SHADER_SRC,
+                GENERATE_CIRCLE_FRAGMENT_SHADER_SRC,
+                None,
+            )
+            .unwrap();
 
-        program.add_shader(shader, "circle_ahr_shader");
+            program.add_shader(shader, "circle_ahr_shader");
+        }
     }
 
     fn get_position(&self) -> [[f32; 4]; 4] {
@@ -248,6 +289,36 @@ impl Drawable for Circle {
     fn get_recieve_shadows_strength(&self) -> f32 {
         self.shadow_strength
     }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn draw_recieve_shadows(&self, context: &mut DrawContext) -> bool {
+        draw_circle(
+            recieve_shadows_color(self.shadow_strength, self.light_layers),
+            self.radius,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.shadow_strength(),
+        );
+
+        true
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        draw_circle(
+            emissive_color(self.emissive, self.emissive_intensity),
+            self.radius,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.emissive(),
+        );
+    }
 }
 
 /// A sphere primitive.
@@ -256,6 +327,10 @@ pub struct Sphere {
     radius: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    emissive: [f32; 3],
+    emissive_intensity: f32,
+    light_layers: u8,
+    blend_mode: BlendMode,
 }
 
 impl Sphere {
@@ -266,43 +341,54 @@ impl Sphere {
             radius,
             transform,
             shadow_strength: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            emissive_intensity: 0.0,
+            light_layers: DEFAULT_LIGHT_LAYERS,
+            blend_mode: BlendMode::Normal,
         }
     }
+
+    /// Make the sphere glow with the given color, an intensity of 0.0 turns the glow off.
+    /// The glow is never shadowed
+    pub fn set_emissive(&mut self, color: [f32; 3], intensity: f32) {
+        self.emissive = color;
+        self.emissive_intensity = intensity;
+    }
+
+    /// Set the light layers the sphere is on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how the sphere is blended with what is beneath it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
 }
 
 impl Drawable for Sphere {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
+    fn draw(&self, context: &mut DrawContext) {
         draw_sphere(
             self.color,
             self.radius,
-            matrix_transform,
-            program,
-            albedo_framebuffer,
-            height_framebuffer,
-            normal_framebuffer,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer,
+            self.blend_mode,
         );
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
-        // this assumes both shaders will always be loaded together
-        if program.get_shader("sphere_height_shader").is_none() {
+        if program.get_shader("sphere_gbuffer_shader").is_none() {
             let shader = glium::Program::from_source(
                 &program.display,
-                GENERATE_SPHERE_HEIGHT_VERTEX_SHADER_SRC,
-                GENERATE_SPHERE_HEIGHT_FRAGMENT_SHADER_SRC,
+                BASE_VERTEX_SHADER_SRC,
+                &preprocess_shader(SPHERE_GBUFFER_FRAGMENT_SHADER_SRC).unwrap(),
                 None,
             )
             .unwrap();
 
-            program.add_shader(shader, "sphere_height_shader");
+            program.add_shader(shader, "sphere_gbuffer_shader");
         }
 
         if program.get_shader("circle_ahr_shader").is_none() {
@@ -316,18 +402,6 @@ impl Drawable for Sphere {
 
             program.add_shader(shader, "circle_ahr_shader");
         }
-
-        if program.get_shader("sphere_normal_shader").is_none() {
-            let shader = glium::Program::from_source(
-                &program.display,
-                GENERATE_SPHERE_NORMAL_VERTEX_SHADER_SRC,
-                GENERATE_SPHERE_NORMAL_FRAGMENT_SHADER_SRC,
-                None,
-            )
-            .unwrap();
-
-            program.add_shader(shader, "sphere_normal_shader");
-        }
     }
 
     fn get_position(&self) -> [[f32; 4]; 4] {
@@ -337,6 +411,36 @@ impl Drawable for Sphere {
     fn get_recieve_shadows_strength(&self) -> f32 {
         self.shadow_strength
     }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn draw_recieve_shadows(&self, context: &mut DrawContext) -> bool {
+        draw_circle(
+            recieve_shadows_color(self.shadow_strength, self.light_layers),
+            self.radius,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.shadow_strength(),
+        );
+
+        true
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        draw_circle(
+            emissive_color(self.emissive, self.emissive_intensity),
+            self.radius,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.emissive(),
+        );
+    }
 }
 
 /// A rectangle primitive.
@@ -346,6 +450,10 @@ pub struct Rectangle {
     height: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    emissive: [f32; 3],
+    emissive_intensity: f32,
+    light_layers: u8,
+    blend_mode: BlendMode,
 }
 
 impl Rectangle {
@@ -357,44 +465,68 @@ impl Rectangle {
             height,
             transform,
             shadow_strength: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            emissive_intensity: 0.0,
+            light_layers: DEFAULT_LIGHT_LAYERS,
+            blend_mode: BlendMode::Normal,
         }
     }
+
+    /// Make the rectangle glow with the given color, an intensity of 0.0 turns the glow off.
+    /// The glow is never shadowed
+    pub fn set_emissive(&mut self, color: [f32; 3], intensity: f32) {
+        self.emissive = color;
+        self.emissive_intensity = intensity;
+    }
+
+    /// Set the light layers the rectangle is on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how the rectangle is blended with what is beneath it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
 }
 
 impl Drawable for Rectangle {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
-        draw_rectangle(
+    fn draw(&self, context: &mut DrawContext) {
+        draw_rectangle_gbuffer(
             self.color,
             self.width,
             self.height,
-            matrix_transform,
-            program,
-            albedo_framebuffer,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer,
+            self.blend_mode,
         );
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
-        if program.get_shader("rectangle_ahr_shader").is_some() {
-            return;
+        if program.get_shader("rectangle_gbuffer_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                BASE_VERTEX_SHADER_SRC,
+                &preprocess_shader(RECTANGLE_GBUFFER_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(shader, "rectangle_gbuffer_shader");
         }
 
-        let shader = glium::Program::from_source(
-            &program.display,
-            GENERATE_RECTANGLE_VERTEX_SHADER_SRC,
-            GENERATE_RECTANGLE_FRAGMENT_SHADER_SRC,
-            None,
-        )
-        .unwrap();
+        if program.get_shader("rectangle_ahr_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                GENERATE_RECTANGLE_VERTEX_SHADER_SRC,
+                GENERATE_RECTANGLE_FRAGMENT_SHADER_SRC,
+                None,
+            )
+            .unwrap();
 
-        program.add_shader(shader, "rectangle_ahr_shader");
+            program.add_shader(shader, "rectangle_ahr_shader");
+        }
     }
 
     fn get_position(&self) -> [[f32; 4]; 4] {
@@ -404,6 +536,38 @@ impl Drawable for Rectangle {
     fn get_recieve_shadows_strength(&self) -> f32 {
         self.shadow_strength
     }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn draw_recieve_shadows(&self, context: &mut DrawContext) -> bool {
+        draw_rectangle(
+            recieve_shadows_color(self.shadow_strength, self.light_layers),
+            self.width,
+            self.height,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.shadow_strength(),
+        );
+
+        true
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        draw_rectangle(
+            emissive_color(self.emissive, self.emissive_intensity),
+            self.width,
+            self.height,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.emissive(),
+        );
+    }
 }
 
 /// A cylinder primitive.
@@ -413,6 +577,10 @@ pub struct Cylinder {
     height: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    emissive: [f32; 3],
+    emissive_intensity: f32,
+    light_layers: u8,
+    blend_mode: BlendMode,
 }
 
 impl Cylinder {
@@ -424,6 +592,10 @@ impl Cylinder {
             height,
             transform,
             shadow_strength: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            emissive_intensity: 0.0,
+            light_layers: DEFAULT_LIGHT_LAYERS,
+            blend_mode: BlendMode::Normal,
         }
     }
 
@@ -431,65 +603,61 @@ impl Cylinder {
     pub fn set_shadow_strength(&mut self, shadow_strength: f32) {
         self.shadow_strength = shadow_strength;
     }
+
+    /// Make the cylinder glow with the given color, an intensity of 0.0 turns the glow off.
+    /// The glow is never shadowed
+    pub fn set_emissive(&mut self, color: [f32; 3], intensity: f32) {
+        self.emissive = color;
+        self.emissive_intensity = intensity;
+    }
+
+    /// Set the light layers the cylinder is on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how the cylinder is blended with what is beneath it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
 }
 
 impl Drawable for Cylinder {
-    fn draw(
-        &self,
-        program: &LumenpyxProgram,
-        matrix_transform: [[f32; 4]; 4],
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
+    fn draw(&self, context: &mut DrawContext) {
         draw_cylinder(
             self.color,
             self.radius,
             self.height,
-            matrix_transform,
-            program,
-            albedo_framebuffer,
-            height_framebuffer,
-            normal_framebuffer,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer,
+            self.blend_mode,
         );
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
-        if program.get_shader("rectangle_ahr_shader").is_none() {
-            let shader = glium::Program::from_source(
-                &program.display,
-                GENERATE_RECTANGLE_VERTEX_SHADER_SRC,
-                GENERATE_RECTANGLE_FRAGMENT_SHADER_SRC,
-                None,
-            )
-            .unwrap();
-
-            program.add_shader(shader, "rectangle_ahr_shader");
-        }
-
-        if program.get_shader("cylinder_height_shader").is_none() {
+        if program.get_shader("cylinder_gbuffer_shader").is_none() {
             let shader = glium::Program::from_source(
                 &program.display,
-                GENERATE_CYLINDER_HEIGHT_VERTEX_SHADER_SRC,
-                GENERATE_CYLINDER_HEIGHT_FRAGMENT_SHADER_SRC,
+                BASE_VERTEX_SHADER_SRC,
+                &preprocess_shader(CYLINDER_GBUFFER_FRAGMENT_SHADER_SRC).unwrap(),
                 None,
             )
             .unwrap();
 
-            program.add_shader(shader, "cylinder_height_shader");
+            program.add_shader(shader, "cylinder_gbuffer_shader");
         }
 
-        if program.get_shader("cylinder_normal_shader").is_none() {
+        if program.get_shader("rectangle_ahr_shader").is_none() {
             let shader = glium::Program::from_source(
                 &program.display,
-                GENERATE_CYLINDER_NORMAL_VERTEX_SHADER_SRC,
-                GENERATE_CYLINDER_NORMAL_FRAGMENT_SHADER_SRC,
+                GENERATE_RECTANGLE_VERTEX_SHADER_SRC,
+                GENERATE_RECTANGLE_FRAGMENT_SHADER_SRC,
                 None,
             )
             .unwrap();
 
-            program.add_shader(shader, "cylinder_normal_shader");
+            program.add_shader(shader, "rectangle_ahr_shader");
         }
     }
 
@@ -500,6 +668,38 @@ impl Drawable for Cylinder {
     fn get_recieve_shadows_strength(&self) -> f32 {
         self.shadow_strength
     }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn draw_recieve_shadows(&self, context: &mut DrawContext) -> bool {
+        draw_rectangle(
+            recieve_shadows_color(self.shadow_strength, self.light_layers),
+            self.radius * 2.0,
+            self.height,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.shadow_strength(),
+        );
+
+        true
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        draw_rectangle(
+            emissive_color(self.emissive, self.emissive_intensity),
+            self.radius * 2.0,
+            self.height,
+            context.transform_matrix,
+            context.program,
+            context.gbuffer.emissive(),
+        );
+    }
 }
 
 fn draw_cylinder(
@@ -508,71 +708,21 @@ fn draw_cylinder(
     height: f32,
     matrix_transform: [[f32; 4]; 4],
     program: &LumenpyxProgram,
-    albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    gbuffer: &mut GBuffer,
+    blend_mode: BlendMode,
 ) {
-    draw_rectangle(
-        color,
-        radius * 2.0,
-        height,
-        matrix_transform,
-        program,
-        albedo_framebuffer,
-    );
-
-    let display = &program.display;
-    let indices = &program.indices;
-
-    let shader = program.get_shader("cylinder_height_shader").unwrap();
-
-    let shape = FULL_SCREEN_QUAD;
-
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
-
-    let uniforms = &uniform! {
-        width: radius * 2.0,
-        height: height,
-        matrix: matrix_transform,
-    };
-
-    height_framebuffer
-        .draw(
-            &vertex_buffer,
-            indices,
-            &shader,
-            uniforms,
-            &Default::default(),
-        )
-        .unwrap();
-
-    let normal_shader = program.get_shader("cylinder_normal_shader").unwrap();
-
-    let shape = FULL_SCREEN_QUAD;
-
-    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
-
-    let resolution = [
-        normal_framebuffer.get_dimensions().0 as f32,
-        normal_framebuffer.get_dimensions().1 as f32,
-    ];
+    let shader = program.get_shader("cylinder_gbuffer_shader").unwrap();
 
+    let dimensions = gbuffer.get_dimensions();
     let uniforms = &uniform! {
+        cylinder_color: color,
         width: radius * 2.0,
-        height: height,
-        resolution: resolution,
+        height_on_screen: height,
         matrix: matrix_transform,
+        resolution: [dimensions[0] as f32, dimensions[1] as f32],
     };
 
-    normal_framebuffer
-        .draw(
-            &vertex_buffer,
-            indices,
-            &normal_shader,
-            uniforms,
-            &Default::default(),
-        )
-        .unwrap();
+    gbuffer.draw_blended(program, shader, uniforms, blend_mode);
 }
 
 pub enum Texture {
@@ -628,6 +778,97 @@ impl From<u8> for Texture {
     }
 }
 
+impl Texture {
+    /// Load the texture as is, solid colors become a 1x1 texture
+    pub(crate) fn to_texture2d(
+        &self,
+        display: &glium::Display<WindowSurface>,
+    ) -> glium::texture::Texture2d {
+        match self {
+            Texture::Path(path) => {
+                let image = load_image(path.as_str());
+                glium::texture::Texture2d::new(display, image).unwrap()
+            }
+            Texture::Solid(color) => {
+                let image = glium::texture::RawImage2d::from_raw_rgba(color.to_vec(), (1, 1));
+                glium::texture::Texture2d::new(display, image).unwrap()
+            }
+        }
+    }
+}
+
+/// The material of a sprite, this decides how each pixel reflects the scene.
+/// Every channel is read from the red channel of its texture.
+pub enum Material {
+    /// A single roughness map from older versions of lumenpyx.
+    /// White is a perfect mirror, so it is mapped to the reflectivity
+    /// with no roughness and no metalness.
+    Legacy(Texture),
+    /// Separate material channels
+    Separate {
+        /// How much of the reflection shows, 1.0 is a mirror
+        reflectivity: Texture,
+        /// How blurry the reflection is, 0.0 is perfectly glossy
+        roughness: Texture,
+        /// How metallic the surface is, metals tint their reflections
+        /// with the albedo and have less diffuse lighting
+        metalness: Texture,
+    },
+}
+
+impl Material {
+    /// Create a new material with separate reflectivity, roughness and metalness
+    pub fn new(reflectivity: Texture, roughness: Texture, metalness: Texture) -> Material {
+        Material::Separate {
+            reflectivity,
+            roughness,
+            metalness,
+        }
+    }
+}
+
+impl From<Texture> for Material {
+    fn from(texture: Texture) -> Self {
+        Material::Legacy(texture)
+    }
+}
+
+impl From<String> for Material {
+    fn from(path: String) -> Self {
+        Material::Legacy(path.into())
+    }
+}
+
+impl From<&str> for Material {
+    fn from(path: &str) -> Self {
+        Material::Legacy(path.into())
+    }
+}
+
+impl From<[f32; 4]> for Material {
+    fn from(color: [f32; 4]) -> Self {
+        Material::Legacy(color.into())
+    }
+}
+
+impl From<[u8; 4]> for Material {
+    fn from(color: [u8; 4]) -> Self {
+        Material::Legacy(color.into())
+    }
+}
+
+impl From<f32> for Material {
+    fn from(value: f32) -> Self {
+        Material::Legacy(value.into())
+    }
+}
+
+impl From<u8> for Material {
+    fn from(value: u8) -> Self {
+        Material::Legacy(value.into())
+    }
+}
+
 pub enum Normal {
     /// Path to a normal map
     Path(String),
@@ -666,31 +907,168 @@ pub struct Sprite {
     height_texture: glium::texture::Texture2d,
     roughness_texture: glium::texture::Texture2d,
     normal_texture: glium::texture::Texture2d,
+    emissive_texture: glium::texture::Texture2d,
     pub transform: Transform,
     shadow_strength: f32,
+    emissive_intensity: f32,
+    emissive_lights: Option<EmissiveLights>,
+    light_layers: u8,
+    blend_mode: BlendMode,
+    recieve_mask: Option<glium::texture::Texture2d>,
+}
+
+/// How far above the glowing pixels the emissive lights are placed,
+/// so they aren't shadowed by the pixels they come from
+const EMISSIVE_LIGHT_HEIGHT_OFFSET: f32 = 0.01;
+
+/// The settings and clusters for the lights cast by the glowing pixels of a sprite
+struct EmissiveLights {
+    intensity: f32,
+    falloff: f32,
+    clusters: Vec<EmissiveCluster>,
+}
+
+/// A group of glowing pixels that acts as one light
+struct EmissiveCluster {
+    /// the center of the glowing pixels in texture coordinates
+    position: [f32; 2],
+    height: f32,
+    color: [f32; 3],
+    intensity: f32,
+}
+
+/// Group the glowing pixels into clusters of cluster_size pixels,
+/// doubling the cluster size until there are no more than max_clusters of them
+fn cluster_emissive_pixels(
+    emissive: &[Vec<(u8, u8, u8, u8)>],
+    height: &[Vec<(u8, u8, u8, u8)>],
+    cluster_size: usize,
+    max_clusters: usize,
+) -> Vec<EmissiveCluster> {
+    if max_clusters == 0 {
+        return vec![];
+    }
+
+    let emissive_height = emissive.len();
+    let emissive_width = emissive.first().map_or(0, |row| row.len());
+
+    // the intensity is always spread over the requested cluster size,
+    // so bigger clusters are brighter and the scene gets the same amount of light
+    let cluster_area = (cluster_size * cluster_size) as f32;
+
+    let mut grown_cluster_size = cluster_size;
+    loop {
+        let clusters =
+            cluster_emissive_pixels_with_size(emissive, height, grown_cluster_size, cluster_area);
+
+        // one cluster covering the whole texture can't be grown any further
+        if clusters.len() <= max_clusters
+            || grown_cluster_size >= emissive_width.max(emissive_height)
+        {
+            return clusters;
+        }
+        grown_cluster_size *= 2;
+    }
+}
+
+fn cluster_emissive_pixels_with_size(
+    emissive: &[Vec<(u8, u8, u8, u8)>],
+    height: &[Vec<(u8, u8, u8, u8)>],
+    cluster_size: usize,
+    cluster_area: f32,
+) -> Vec<EmissiveCluster> {
+    let emissive_height = emissive.len();
+    let emissive_width = emissive.first().map_or(0, |row| row.len());
+    let height_height = height.len();
+    let height_width = height.first().map_or(0, |row| row.len());
+
+    let mut clusters = vec![];
+    for cluster_y in (0..emissive_height).step_by(cluster_size) {
+        for cluster_x in (0..emissive_width).step_by(cluster_size) {
+            let mut total_brightness = 0.0;
+            let mut total_color = [0.0; 3];
+            let mut total_position = [0.0; 2];
+            let mut total_height = 0.0;
+
+            for (y, row) in emissive
+                .iter()
+                .enumerate()
+                .skip(cluster_y)
+                .take(cluster_size)
+            {
+                for (x, &(r, g, b, a)) in row.iter().enumerate().skip(cluster_x).take(cluster_size)
+                {
+                    let alpha = a as f32 / 255.0;
+                    let color = [
+                        r as f32 / 255.0 * alpha,
+                        g as f32 / 255.0 * alpha,
+                        b as f32 / 255.0 * alpha,
+                    ];
+                    let brightness = color[0].max(color[1]).max(color[2]);
+                    if brightness <= 0.0 {
+                        continue;
+                    }
+
+                    let u = (x as f32 + 0.5) / emissive_width as f32;
+                    let v = (y as f32 + 0.5) / emissive_height as f32;
+
+                    // the heightmap doesn't have to be the same size as the emissive map
+                    let pixel_height = if height_width > 0 && height_height > 0 {
+                        let height_x = ((u * height_width as f32) as usize).min(height_width - 1);
+                        let height_y = ((v * height_height as f32) as usize).min(height_height - 1);
+                        height[height_y][height_x].0 as f32 / 255.0
+                    } else {
+                        0.0
+                    };
+
+                    total_brightness += brightness;
+                    for (total, channel) in total_color.iter_mut().zip(color) {
+                        *total += channel;
+                    }
+                    total_position[0] += u * brightness;
+                    total_position[1] += v * brightness;
+                    total_height += pixel_height * brightness;
+                }
+            }
+
+            if total_brightness <= 0.0 {
+                continue;
+            }
+
+            // the color is normalized and the brightness goes into the intensity
+            let max_channel = total_color[0].max(total_color[1]).max(total_color[2]);
+            clusters.push(EmissiveCluster {
+                position: [
+                    total_position[0] / total_brightness,
+                    total_position[1] / total_brightness,
+                ],
+                height: total_height / total_brightness,
+                color: [
+                    total_color[0] / max_channel,
+                    total_color[1] / max_channel,
+                    total_color[2] / max_channel,
+                ],
+                intensity: total_brightness / cluster_area,
+            });
+        }
+    }
+
+    clusters
 }
 
 impl Sprite {
     pub fn new(
         albedo: Texture,
         height: Texture,
-        roughness: Texture,
+        material: Material,
         normal: Normal,
+        emissive: Texture,
         program: &LumenpyxProgram,
         transform: Transform,
     ) -> Sprite {
         let display = &program.display;
 
-        let albedo_texture = match albedo {
-            Texture::Path(path) => {
-                let image = load_image(path.as_str());
-                glium::texture::Texture2d::new(display, image).unwrap()
-            }
-            Texture::Solid(color) => {
-                let image = glium::texture::RawImage2d::from_raw_rgba(color.to_vec(), (1, 1));
-                glium::texture::Texture2d::new(display, image).unwrap()
-            }
-        };
+        let albedo_texture = albedo.to_texture2d(display);
         let height_texture = match height {
             Texture::Path(path) => {
                 let image = load_image(path.as_str());
@@ -703,17 +1081,27 @@ impl Sprite {
                 shaders::new_fill_alpha_texure(program, albedo_sampler, color)
             }
         };
-        let roughness_texture = match roughness {
-            Texture::Path(path) => {
-                let image = load_image(path.as_str());
-                glium::texture::Texture2d::new(display, image).unwrap()
-            }
-            Texture::Solid(color) => {
-                let albedo_sampler =
-                    glium::uniforms::Sampler(&albedo_texture, crate::DEFAULT_BEHAVIOR);
-
-                shaders::new_fill_alpha_texure(program, albedo_sampler, color)
-            }
+        let roughness_texture = {
+            let (reflectivity, roughness, metalness) = match material {
+                Material::Legacy(texture) => (texture, Texture::from(0.0), Texture::from(0.0)),
+                Material::Separate {
+                    reflectivity,
+                    roughness,
+                    metalness,
+                } => (reflectivity, roughness, metalness),
+            };
+
+            let reflectivity = reflectivity.to_texture2d(display);
+            let roughness = roughness.to_texture2d(display);
+            let metalness = metalness.to_texture2d(display);
+
+            shaders::new_material_texture(
+                program,
+                glium::uniforms::Sampler(&albedo_texture, crate::DEFAULT_BEHAVIOR),
+                glium::uniforms::Sampler(&reflectivity, crate::DEFAULT_BEHAVIOR),
+                glium::uniforms::Sampler(&roughness, crate::DEFAULT_BEHAVIOR),
+                glium::uniforms::Sampler(&metalness, crate::DEFAULT_BEHAVIOR),
+            )
         };
 
         let normal_texture = match normal {
@@ -757,98 +1145,227 @@ impl Sprite {
             }
         };
 
+        let emissive_texture = match emissive {
+            Texture::Path(path) => {
+                let image = load_image(path.as_str());
+                glium::texture::Texture2d::new(display, image).unwrap()
+            }
+            Texture::Solid(color) => {
+                let albedo_sampler =
+                    glium::uniforms::Sampler(&albedo_texture, crate::DEFAULT_BEHAVIOR);
+
+                shaders::new_fill_alpha_texure(program, albedo_sampler, color)
+            }
+        };
+
         Sprite {
             albedo_texture,
             height_texture,
             roughness_texture,
             normal_texture,
+            emissive_texture,
             transform,
             shadow_strength: 0.5,
+            emissive_intensity: 1.0,
+            emissive_lights: None,
+            light_layers: DEFAULT_LIGHT_LAYERS,
+            blend_mode: BlendMode::Normal,
+            recieve_mask: None,
         }
     }
 
     pub fn set_shadow_strength(&mut self, strength: f32) {
         self.shadow_strength = strength;
     }
-}
 
-impl Drawable for Sprite {
-    fn draw(
+    /// Set a mask for how much each pixel of the sprite receives shadows and light,
+    /// the red channel is multiplied with the shadow strength and the green channel with the light,
+    /// so glowing runes on a wall can ignore shadows while the stone around them doesn't
+    /// None receives them evenly
+    pub fn set_recieve_mask(&mut self, mask: Option<Texture>, program: &LumenpyxProgram) {
+        self.recieve_mask = mask.map(|mask| mask.to_texture2d(&program.display));
+    }
+
+    /// scale the transform matrix to match the size of the texture
+    fn adjust_transform_for_texture(
         &self,
-        program: &LumenpyxProgram,
         transform_matrix: [[f32; 4]; 4],
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
-        let indices = &program.indices;
-        let display = &program.display;
+        framebuffer_dimensions: (u32, u32),
+    ) -> [[f32; 4]; 4] {
+        // check which side is longer and scale the other side to match
+        let width = self.albedo_texture.get_width() as f32;
+        let height = self.albedo_texture.get_height().unwrap() as f32;
+        let mut transform_matrix = transform_matrix;
 
-        let shader = program.get_shader("sprite_shader").unwrap();
+        let smallest_dimension =
+            (framebuffer_dimensions.1 as f32).min(framebuffer_dimensions.0 as f32);
+        let x_scale = width as f32 / smallest_dimension;
+        let y_scale = height as f32 / smallest_dimension;
 
-        let shape = FULL_SCREEN_QUAD;
+        transform_matrix[0][0] *= x_scale;
+        transform_matrix[1][1] *= y_scale;
 
-        let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+        transform_matrix
+    }
 
-        let mut image = glium::uniforms::Sampler(&self.albedo_texture, DEFAULT_BEHAVIOR);
+    /// Set how bright the emissive texture is, this can go above 1.0 for bloom
+    pub fn set_emissive_intensity(&mut self, intensity: f32) {
+        self.emissive_intensity = intensity;
+    }
 
-        // scale the transform matrix to match the size of the texture
-        // check which side is longer and scale the other side to match
-        let width = self.albedo_texture.get_width() as f32;
-        let height = self.albedo_texture.get_height().unwrap() as f32;
-        let mut transform_matrix = transform_matrix;
+    /// Turn the glowing pixels of the sprite into point lights that light up the scene around it.
+    /// The emissive texture is split into squares of cluster_size pixels, every square that glows
+    /// becomes one light at the center of its glowing pixels, so the lights move with the sprite.
+    /// If that would make more than max_lights lights the squares are doubled in size until they fit,
+    /// the lights stay as bright overall, they are just spread over fewer of them.
+    /// This reads the textures back from the gpu, so call it once rather than every frame
+    pub fn enable_emissive_lights(
+        &mut self,
+        cluster_size: u32,
+        max_lights: u32,
+        intensity: f32,
+        falloff: f32,
+    ) {
+        let emissive: Vec<Vec<(u8, u8, u8, u8)>> = self.emissive_texture.read();
+        let height: Vec<Vec<(u8, u8, u8, u8)>> = self.height_texture.read();
+
+        self.emissive_lights = Some(EmissiveLights {
+            intensity,
+            falloff,
+            clusters: cluster_emissive_pixels(
+                &emissive,
+                &height,
+                cluster_size.max(1) as usize,
+                max_lights as usize,
+            ),
+        });
+    }
 
-        // adjust size of the sprite to match the texture
-        {
-            let smallest_dimension = (albedo_framebuffer.get_dimensions().1 as f32)
-                .min(albedo_framebuffer.get_dimensions().0 as f32);
-            let x_scale = width as f32 / smallest_dimension;
-            let y_scale = height as f32 / smallest_dimension;
+    /// Stop the glowing pixels of the sprite from lighting up the scene
+    pub fn disable_emissive_lights(&mut self) {
+        self.emissive_lights = None;
+    }
 
-            transform_matrix[0][0] *= x_scale;
-            transform_matrix[1][1] *= y_scale;
-        }
+    /// Set the light layers the sprite is on as a bitmask, bit i is layer i
+    pub fn set_light_layers(&mut self, light_layers: u8) {
+        self.light_layers = light_layers;
+    }
+
+    /// Set how the sprite is blended with what is beneath it
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+}
+
+impl Drawable for Sprite {
+    fn draw(&self, context: &mut DrawContext) {
+        let program = context.program;
+        let shader = program.get_shader("sprite_gbuffer_shader").unwrap();
+
+        // adjust size of the sprite to match the texture
+        let dimensions = context.gbuffer.get_dimensions();
+        let transform_matrix = self
+            .adjust_transform_for_texture(context.transform_matrix, (dimensions[0], dimensions[1]));
 
         let uniform = &uniform! {
             matrix: transform_matrix,
-            image: image,
+            albedo_map: glium::uniforms::Sampler(&self.albedo_texture, DEFAULT_BEHAVIOR),
+            height_map: glium::uniforms::Sampler(&self.height_texture, DEFAULT_BEHAVIOR),
+            material_map: glium::uniforms::Sampler(&self.roughness_texture, DEFAULT_BEHAVIOR),
+            normal_map: glium::uniforms::Sampler(&self.normal_texture, DEFAULT_BEHAVIOR),
         };
 
-        albedo_framebuffer
-            .draw(
-                &vertex_buffer,
-                indices,
-                &shader,
-                uniform,
-                &Default::default(),
+        context
+            .gbuffer
+            .draw_blended(program, shader, uniform, self.blend_mode);
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        if program.get_shader("sprite_gbuffer_shader").is_none() {
+            let new_shader = glium::Program::from_source(
+                &program.display,
+                BASE_VERTEX_SHADER_SRC,
+                &preprocess_shader(SPRITE_GBUFFER_FRAGMENT_SHADER_SRC).unwrap(),
+                None,
             )
             .unwrap();
 
-        image = glium::uniforms::Sampler(&self.height_texture, DEFAULT_BEHAVIOR);
-        let uniform = &uniform! {
-            matrix: transform_matrix,
-            image: image,
-        };
-        height_framebuffer
-            .draw(
-                &vertex_buffer,
-                indices,
-                &shader,
-                uniform,
-                &Default::default(),
+            program.add_shader(new_shader, "sprite_gbuffer_shader");
+        }
+
+        if program.get_shader("sprite_emissive_shader").is_none() {
+            let new_shader = glium::Program::from_source(
+                &program.display,
+                BASE_VERTEX_SHADER_SRC,
+                SPRITE_EMISSIVE_FRAGMENT_SHADER_SRC,
+                None,
             )
             .unwrap();
 
-        image = glium::uniforms::Sampler(&self.roughness_texture, DEFAULT_BEHAVIOR);
+            program.add_shader(new_shader, "sprite_emissive_shader");
+        }
+
+        if program.get_shader("sprite_recieve_mask_shader").is_none() {
+            let new_shader = glium::Program::from_source(
+                &program.display,
+                BASE_VERTEX_SHADER_SRC,
+                SPRITE_RECIEVE_MASK_FRAGMENT_SHADER_SRC,
+                None,
+            )
+            .unwrap();
+
+            program.add_shader(new_shader, "sprite_recieve_mask_shader");
+        }
+    }
+
+    fn get_position(&self) -> [[f32; 4]; 4] {
+        self.transform.matrix
+    }
+
+    /// 0.0 is no shadows, 1.0 is full shadows
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.shadow_strength
+    }
+
+    fn get_light_layers(&self) -> u8 {
+        self.light_layers
+    }
+
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn draw_recieve_shadows(&self, context: &mut DrawContext) -> bool {
+        let program = context.program;
+        let shadow_strength_framebuffer = context.gbuffer.shadow_strength();
+        let indices = &program.indices;
+
+        // sprites without a mask receive shadows and light evenly
+        let mask = match &self.recieve_mask {
+            Some(mask) => mask,
+            None => program.get_blank_texture(),
+        };
+
+        let shader = program.get_shader("sprite_recieve_mask_shader").unwrap();
+
+        let vertex_buffer = program.get_full_screen_quad();
+
+        let transform_matrix = self.adjust_transform_for_texture(
+            context.transform_matrix,
+            shadow_strength_framebuffer.get_dimensions(),
+        );
+
         let uniform = &uniform! {
             matrix: transform_matrix,
-            image: image,
+            mask: glium::uniforms::Sampler(mask, DEFAULT_BEHAVIOR),
+            coverage: glium::uniforms::Sampler(&self.albedo_texture, DEFAULT_BEHAVIOR),
+            shadow_strength: self.shadow_strength,
+            light_layers: self.light_layers as f32 / 255.0,
         };
 
-        roughness_framebuffer
+        shadow_strength_framebuffer
             .draw(
-                &vertex_buffer,
+                vertex_buffer,
                 indices,
                 &shader,
                 uniform,
@@ -856,44 +1373,164 @@ impl Drawable for Sprite {
             )
             .unwrap();
 
-        image = glium::uniforms::Sampler(&self.normal_texture, DEFAULT_BEHAVIOR);
+        true
+    }
+
+    fn get_emissive_lights(
+        &self,
+        program: &LumenpyxProgram,
+        transform_matrix: [[f32; 4]; 4],
+    ) -> Vec<PointLight> {
+        let emissive_lights = match &self.emissive_lights {
+            Some(emissive_lights) => emissive_lights,
+            None => return vec![],
+        };
+
+        let matrix = self.adjust_transform_for_texture(
+            transform_matrix,
+            (program.dimensions[0], program.dimensions[1]),
+        );
+
+        emissive_lights
+            .clusters
+            .iter()
+            .map(|cluster| {
+                // move the cluster from texture coordinates to the sprite's quad, then transform it
+                let x = cluster.position[0] * 2.0 - 1.0;
+                let y = cluster.position[1] * 2.0 - 1.0;
+                let position = [
+                    matrix[0][0] * x + matrix[1][0] * y + matrix[3][0],
+                    matrix[0][1] * x + matrix[1][1] * y + matrix[3][1],
+                    cluster.height + EMISSIVE_LIGHT_HEIGHT_OFFSET,
+                ];
+
+                PointLight::new(
+                    position,
+                    cluster.color,
+                    cluster.intensity * emissive_lights.intensity * self.emissive_intensity,
+                    emissive_lights.falloff,
+                )
+            })
+            .collect()
+    }
+
+    fn draw_emissive(&self, context: &mut DrawContext) {
+        let program = context.program;
+        let emissive_framebuffer = context.gbuffer.emissive();
+        let indices = &program.indices;
+
+        let shader = program.get_shader("sprite_emissive_shader").unwrap();
+
+        let vertex_buffer = program.get_full_screen_quad();
+
+        let transform_matrix = self.adjust_transform_for_texture(
+            context.transform_matrix,
+            emissive_framebuffer.get_dimensions(),
+        );
+
         let uniform = &uniform! {
             matrix: transform_matrix,
-            image: image,
+            image: glium::uniforms::Sampler(&self.emissive_texture, DEFAULT_BEHAVIOR),
+            coverage: glium::uniforms::Sampler(&self.albedo_texture, DEFAULT_BEHAVIOR),
+            intensity: self.emissive_intensity,
         };
-        normal_framebuffer
-            .draw(
-                &vertex_buffer,
-                indices,
-                &shader,
-                uniform,
-                &Default::default(),
-            )
+
+        emissive_framebuffer
+            .draw(vertex_buffer, indices, shader, uniform, &Default::default())
             .unwrap();
     }
+}
 
-    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
-        if program.get_shader("sprite_shader").is_some() {
-            return;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let new_shader = glium::Program::from_source(
-            &program.display,
-            BASE_VERTEX_SHADER_SRC,
-            BASE_FRAGMENT_SHADER_SRC,
-            None,
-        )
-        .unwrap();
+    fn solid_pixels(
+        width: usize,
+        height: usize,
+        pixel: (u8, u8, u8, u8),
+    ) -> Vec<Vec<(u8, u8, u8, u8)>> {
+        vec![vec![pixel; width]; height]
+    }
+
+    #[test]
+    fn dark_pixels_make_no_clusters() {
+        let emissive = solid_pixels(4, 4, (0, 0, 0, 255));
+        let height = solid_pixels(4, 4, (0, 0, 0, 255));
 
-        program.add_shader(new_shader, "sprite_shader");
+        assert!(cluster_emissive_pixels(&emissive, &height, 1, 100).is_empty());
     }
 
-    fn get_position(&self) -> [[f32; 4]; 4] {
-        self.transform.matrix
+    #[test]
+    fn a_single_glowing_pixel_makes_a_cluster_at_its_center() {
+        let mut emissive = solid_pixels(4, 4, (0, 0, 0, 0));
+        emissive[2][1] = (255, 128, 0, 255);
+        let height = solid_pixels(4, 4, (51, 51, 51, 255));
+
+        let clusters = cluster_emissive_pixels(&emissive, &height, 4, 100);
+
+        assert_eq!(clusters.len(), 1);
+        let cluster = &clusters[0];
+        assert_eq!(cluster.position, [1.5 / 4.0, 2.5 / 4.0]);
+        assert!((cluster.height - 0.2).abs() < 1e-6);
+        assert_eq!(cluster.color[0], 1.0);
+        assert!((cluster.color[1] - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!(cluster.color[2], 0.0);
+        assert_eq!(cluster.intensity, 1.0 / 16.0);
     }
 
-    /// 0.0 is no shadows, 1.0 is full shadows
-    fn get_recieve_shadows_strength(&self) -> f32 {
-        self.shadow_strength
+    #[test]
+    fn transparent_pixels_do_not_glow() {
+        let emissive = solid_pixels(2, 2, (255, 255, 255, 0));
+        let height = solid_pixels(2, 2, (0, 0, 0, 255));
+
+        assert!(cluster_emissive_pixels(&emissive, &height, 1, 100).is_empty());
+    }
+
+    #[test]
+    fn clusters_grow_to_fit_the_budget_and_keep_the_total_intensity() {
+        let emissive = solid_pixels(8, 8, (255, 255, 255, 255));
+        let height = solid_pixels(8, 8, (0, 0, 0, 255));
+
+        let unlimited = cluster_emissive_pixels(&emissive, &height, 1, usize::MAX);
+        let limited = cluster_emissive_pixels(&emissive, &height, 1, 4);
+
+        assert_eq!(unlimited.len(), 64);
+        assert_eq!(limited.len(), 4);
+        let total = |clusters: &[EmissiveCluster]| -> f32 {
+            clusters.iter().map(|cluster| cluster.intensity).sum()
+        };
+        assert!((total(&unlimited) - total(&limited)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clusters_stop_growing_at_the_size_of_the_texture() {
+        let emissive = solid_pixels(5, 3, (255, 255, 255, 255));
+        let height = solid_pixels(5, 3, (0, 0, 0, 255));
+
+        assert_eq!(cluster_emissive_pixels(&emissive, &height, 1, 1).len(), 1);
+    }
+
+    #[test]
+    fn a_budget_of_zero_makes_no_clusters() {
+        let emissive = solid_pixels(4, 4, (255, 255, 255, 255));
+        let height = solid_pixels(4, 4, (0, 0, 0, 255));
+
+        assert!(cluster_emissive_pixels(&emissive, &height, 1, 0).is_empty());
+    }
+
+    #[test]
+    fn primitives_default_to_the_normal_blend_mode() {
+        let transform = Transform::new([0.0, 0.0, 0.0]);
+        let primitives: [&dyn Drawable; 4] = [
+            &Circle::new([1.0; 4], 0.5, transform),
+            &Sphere::new([1.0; 4], 0.5, transform),
+            &Rectangle::new([1.0; 4], 0.5, 0.5, transform),
+            &Cylinder::new([1.0; 4], 0.5, 0.5, transform),
+        ];
+
+        for primitive in primitives {
+            assert_eq!(primitive.get_blend_mode(), BlendMode::Normal);
+        }
     }
 }
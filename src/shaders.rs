@@ -1,5 +1,8 @@
 use crate::primitives::{BASE_FRAGMENT_SHADER_SRC, BASE_VERTEX_SHADER_SRC};
+use crate::AmbientOcclusionSettings;
+use crate::BloomSettings;
 use crate::Camera;
+use crate::GlobalIlluminationSettings;
 use crate::LumenpyxProgram;
 use crate::Vertex;
 use glium;
@@ -40,6 +43,68 @@ pub(crate) const FILL_ALPHA_VERTEX_SHADER_SRC: &str =
 pub(crate) const FILL_ALPHA_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/technical_shaders/fill_alpha.frag");
 
+pub(crate) const COPY_TEXTURE_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/copy_texture.vert");
+
+pub(crate) const COPY_TEXTURE_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/copy_texture.frag");
+
+pub(crate) const BLOOM_BLUR_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/bloom_blur.vert");
+pub(crate) const BLOOM_BLUR_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/bloom_blur.frag");
+
+pub(crate) const GLOBAL_ILLUMINATION_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/global_illumination.vert");
+pub(crate) const GLOBAL_ILLUMINATION_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/global_illumination.frag");
+
+pub(crate) const AMBIENT_OCCLUSION_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/ambient_occlusion.vert");
+pub(crate) const AMBIENT_OCCLUSION_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/ambient_occlusion.frag");
+
+pub(crate) const AMBIENT_LIGHT_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/ambient_light.vert");
+pub(crate) const AMBIENT_LIGHT_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/ambient_light.frag");
+
+pub(crate) const SHADOWS_GLSL_SRC: &str = include_str!("../shaders/shading/lighting/shadows.glsl");
+pub(crate) const COOKIE_GLSL_SRC: &str = include_str!("../shaders/shading/lighting/cookie.glsl");
+pub(crate) const LIGHT_LAYERS_GLSL_SRC: &str =
+    include_str!("../shaders/shading/lighting/light_layers.glsl");
+pub(crate) const FALLOFF_GLSL_SRC: &str = include_str!("../shaders/shading/lighting/falloff.glsl");
+pub(crate) const HEIGHT_TRACE_GLSL_SRC: &str = include_str!("../shaders/shading/height_trace.glsl");
+
+pub(crate) const GBUFFER_BLEND_GLSL_SRC: &str =
+    include_str!("../shaders/primitives/gbuffer_blend.glsl");
+
+/// the snippets that a shader can pull in with #include "name"
+const GLSL_SNIPPETS: [(&str, &str); 6] = [
+    ("shadows.glsl", SHADOWS_GLSL_SRC),
+    ("height_trace.glsl", HEIGHT_TRACE_GLSL_SRC),
+    ("cookie.glsl", COOKIE_GLSL_SRC),
+    ("falloff.glsl", FALLOFF_GLSL_SRC),
+    ("light_layers.glsl", LIGHT_LAYERS_GLSL_SRC),
+    ("gbuffer_blend.glsl", GBUFFER_BLEND_GLSL_SRC),
+];
+
+pub(crate) const LOCAL_MAX_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/local_max.vert");
+pub(crate) const LOCAL_MAX_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/local_max.frag");
+
+pub(crate) const LIGHT_UPSAMPLE_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/shading/light_upsample.vert");
+pub(crate) const LIGHT_UPSAMPLE_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/shading/light_upsample.frag");
+
+pub(crate) const PACK_MATERIAL_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/pack_material.vert");
+
+pub(crate) const PACK_MATERIAL_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/pack_material.frag");
+
 /// A full screen quad that can be used to draw to the screen with a shader
 pub const FULL_SCREEN_QUAD: [Vertex; 6] = [
     Vertex {
@@ -151,11 +216,14 @@ pub(crate) fn draw_upscale(
 }
 
 #[no_mangle]
+// one sampler for each texture the reflections read, the metalness tints them with the albedo
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn draw_reflections(
     camera: &Camera,
     lit_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
     height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
-    rougness_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    material_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
     normal_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
     framebuffer: &mut SimpleFrameBuffer,
     program: &LumenpyxProgram,
@@ -174,8 +242,9 @@ pub(crate) fn draw_reflections(
 
     let uniforms = &uniform! {
         albedomap: lit_uniform,
+        basecolormap: albedo_uniform,
         heightmap: height_uniform,
-        roughnessmap: rougness_uniform,
+        materialmap: material_uniform,
         normalmap: normal_uniform,
         camera_z: camera_pos[2],
     };
@@ -278,6 +347,457 @@ pub(crate) fn new_fill_alpha_texure(
     texture
 }
 
+/// fill the mipmaps of the height texture with the max height of the pixels below them
+/// the tracers use this to skip over whole blocks of pixels that are too low to hit
+pub(crate) fn generate_max_height_mipmaps(
+    program: &LumenpyxProgram,
+    height_texture: &glium::texture::Texture2d,
+) {
+    let display = &program.display;
+    let indices = &program.indices;
+    let shader = &program.get_shader("local_max_shader").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    for level in 1..height_texture.get_mipmap_levels() {
+        let width = (height_texture.width() >> level).max(1);
+        let height = (height_texture.height() >> level).max(1);
+
+        // a texture can't be read from and drawn to at the same time,
+        // so every level is drawn to a temporary texture and then copied in
+        let level_texture = glium::texture::Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .unwrap();
+
+        let mut level_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, &level_texture).unwrap();
+
+        let uniforms = &uniform! {
+            high_res_image: glium::uniforms::Sampler(height_texture, crate::HEIGHT_MIPMAP_BEHAVIOR),
+            high_res_level: level as i32 - 1,
+            new_resolution: [width, height],
+        };
+
+        level_framebuffer
+            .draw(
+                vertex_buffer,
+                indices,
+                shader,
+                uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let mipmap_framebuffer = glium::framebuffer::SimpleFrameBuffer::new(
+            display,
+            height_texture.mipmap(level).unwrap(),
+        )
+        .unwrap();
+
+        level_framebuffer.blit_whole_color_to(
+            &mipmap_framebuffer,
+            &glium::BlitTarget {
+                left: 0,
+                bottom: 0,
+                width: width as i32,
+                height: height as i32,
+            },
+            glium::uniforms::MagnifySamplerFilter::Nearest,
+        );
+    }
+}
+
+/// Replace every #include "name" line in the shader with the snippet of that name.
+/// glsl has no includes of its own, so shared code like the shadow tracing lives in snippets,
+/// custom lights can use them to shade the same way as the built-in lights.
+/// The snippets are
+/// - "shadows.glsl": shadow_occlusion, shadow_occlusion_box and apply_shadow,
+///   it needs `uniform sampler2D heightmap;` declared before it
+/// - "light_layers.glsl": in_light_layers and pixel_light_recieve,
+///   it needs `uniform sampler2D shadow_strength_map;` declared before it
+/// - "falloff.glsl": falloff_curve and apply_falloff, the curves of FalloffModel
+/// - "cookie.glsl": sample_cookie
+/// - "height_trace.glsl": trace_heightmap, which shadows.glsl is built on
+/// - "gbuffer_blend.glsl": write_gbuffer, for drawables drawn with GBuffer::draw_blended
+///
+/// Like #pragma once every snippet is only pasted in the first time it is included,
+/// so snippets that include each other can be included in any order.
+/// Including a name that isn't a snippet is an error
+pub fn preprocess_shader(source: &str) -> Result<String, UnknownShaderInclude> {
+    let mut processed = String::with_capacity(source.len());
+    let mut included = vec![];
+    preprocess_shader_into(source, &mut processed, &mut included)?;
+    Ok(processed)
+}
+
+fn preprocess_shader_into(
+    source: &str,
+    processed: &mut String,
+    included: &mut Vec<&'static str>,
+) -> Result<(), UnknownShaderInclude> {
+    for line in source.lines() {
+        match line.trim().strip_prefix("#include") {
+            Some(include) => {
+                let name = include.trim().trim_matches('"');
+                let (snippet_name, snippet) = GLSL_SNIPPETS
+                    .iter()
+                    .find(|(snippet_name, _)| *snippet_name == name)
+                    .ok_or_else(|| UnknownShaderInclude {
+                        name: name.to_string(),
+                    })?;
+
+                if !included.contains(snippet_name) {
+                    included.push(snippet_name);
+                    preprocess_shader_into(snippet, processed, included)?;
+                }
+            }
+            None => processed.push_str(line),
+        }
+        processed.push('\n');
+    }
+    Ok(())
+}
+
+/// A shader included a snippet that doesn't exist, see preprocess_shader for the snippets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownShaderInclude {
+    /// the name that was included
+    pub name: String,
+}
+
+impl std::fmt::Display for UnknownShaderInclude {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "unknown shader include \"{}\"", self.name)
+    }
+}
+
+impl std::error::Error for UnknownShaderInclude {}
+
+/// draw a texture over the whole framebuffer, optionally blending it with what is already there
+pub(crate) fn draw_texture(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    image_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    blend: Option<glium::Blend>,
+) {
+    let indices = &program.indices;
+    let shader = &program.get_shader("copy_texture").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let uniforms = &uniform! {
+        image: image_uniform,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &glium::DrawParameters {
+                blend: blend.unwrap_or_default(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+}
+
+/// blur the emissive texture and add it to the framebuffer
+pub(crate) fn draw_bloom(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    emissive_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    settings: &BloomSettings,
+) {
+    let display = &program.display;
+    let indices = &program.indices;
+    let shader = &program.get_shader("bloom_blur_shader").unwrap();
+    let dimensions = emissive_uniform.0.dimensions();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let blurred_texture = glium::texture::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::F16F16F16F16,
+        glium::texture::MipmapsOption::NoMipmap,
+        dimensions.0,
+        dimensions.1,
+    )
+    .unwrap();
+
+    // the horizontal pass goes into a temporary texture
+    {
+        let mut blurred_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, &blurred_texture).unwrap();
+
+        let uniforms = &uniform! {
+            image: emissive_uniform,
+            direction: [1.0 / dimensions.0 as f32, 0.0],
+            radius: settings.radius as i32,
+            intensity: 1.0f32,
+        };
+
+        blurred_framebuffer
+            .draw(
+                vertex_buffer,
+                indices,
+                shader,
+                uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+
+    // the vertical pass is added straight onto the image
+    let uniforms = &uniform! {
+        image: glium::uniforms::Sampler(&blurred_texture, crate::DEFAULT_BEHAVIOR),
+        direction: [0.0, 1.0 / dimensions.1 as f32],
+        radius: settings.radius as i32,
+        intensity: settings.intensity,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &glium::DrawParameters {
+                blend: crate::lights::DEFAULT_LIGHT_BLENDING,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+}
+
+/// gather the light bouncing off of nearby pixels and add it to the framebuffer
+/// every bounce uses the light gathered by the bounce before it
+pub(crate) fn draw_global_illumination(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    lit_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ambient_occlusion_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    settings: &GlobalIlluminationSettings,
+) {
+    let display = &program.display;
+    let indices = &program.indices;
+    let shader = &program.get_shader("global_illumination_shader").unwrap();
+    let dimensions = albedo_uniform.0.dimensions();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let mut previous_bounce: Option<glium::texture::Texture2d> = None;
+    for _ in 0..settings.bounces {
+        let bounce_texture = glium::texture::Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::F16F16F16F16,
+            glium::texture::MipmapsOption::NoMipmap,
+            dimensions.0,
+            dimensions.1,
+        )
+        .unwrap();
+
+        {
+            let radiance_uniform = match &previous_bounce {
+                Some(texture) => glium::uniforms::Sampler(texture, crate::DEFAULT_BEHAVIOR),
+                None => lit_uniform,
+            };
+
+            let mut bounce_framebuffer =
+                glium::framebuffer::SimpleFrameBuffer::new(display, &bounce_texture).unwrap();
+            bounce_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+
+            let uniforms = &uniform! {
+                radiancemap: radiance_uniform,
+                albedomap: albedo_uniform,
+                heightmap: height_uniform,
+                aomap: ambient_occlusion_uniform,
+                rays: settings.rays as i32,
+                steps: settings.steps as i32,
+                radius: settings.radius,
+                intensity: settings.intensity,
+            };
+
+            bounce_framebuffer
+                .draw(
+                    vertex_buffer,
+                    indices,
+                    shader,
+                    uniforms,
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+
+        draw_texture(
+            framebuffer,
+            program,
+            glium::uniforms::Sampler(&bounce_texture, crate::DEFAULT_BEHAVIOR),
+            Some(crate::lights::DEFAULT_LIGHT_BLENDING),
+        );
+
+        previous_bounce = Some(bounce_texture);
+    }
+}
+
+/// darken the concave parts of the height map, white is fully open and black is fully occluded
+pub(crate) fn draw_ambient_occlusion(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    settings: &AmbientOcclusionSettings,
+) {
+    let indices = &program.indices;
+    let shader = &program.get_shader("ambient_occlusion_shader").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let uniforms = &uniform! {
+        heightmap: height_uniform,
+        albedomap: albedo_uniform,
+        radius: settings.radius,
+        samples: settings.samples as i32,
+        strength: settings.strength,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &Default::default(),
+        )
+        .unwrap();
+}
+
+/// add the ambient light to the framebuffer, darkened by the ambient occlusion
+pub(crate) fn draw_ambient_light(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ambient_occlusion_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ambient_light: [f32; 3],
+) {
+    let indices = &program.indices;
+    let shader = &program.get_shader("ambient_light_shader").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let uniforms = &uniform! {
+        albedomap: albedo_uniform,
+        aomap: ambient_occlusion_uniform,
+        ambient_light: ambient_light,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &glium::DrawParameters {
+                blend: crate::lights::DEFAULT_LIGHT_BLENDING,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+}
+
+/// upsample the low resolution light into the framebuffer and multiply it by the full resolution albedo
+/// low resolution pixels on a different height or facing a different way aren't blended in
+pub(crate) fn draw_light_upsample(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    light_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    normal_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+) {
+    let indices = &program.indices;
+    let shader = &program.get_shader("light_upsample_shader").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let uniforms = &uniform! {
+        lightmap: light_uniform,
+        albedomap: albedo_uniform,
+        heightmap: height_uniform,
+        normalmap: normal_uniform,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &glium::DrawParameters {
+                blend: crate::lights::DEFAULT_LIGHT_BLENDING,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+}
+
+/// packs the reflectivity, roughness and metalness maps into one material texture
+/// the result is the same size as the albedo and uses its alpha
+pub(crate) fn new_material_texture(
+    program: &LumenpyxProgram,
+    albedo_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    reflectivity_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    roughness_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    metalness_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+) -> glium::texture::Texture2d {
+    let display = &program.display;
+    let indices = &program.indices;
+    let dimensions = albedo_sampler.0.dimensions();
+
+    let texture = glium::texture::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::U8U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap,
+        dimensions.0,
+        dimensions.1,
+    )
+    .unwrap();
+
+    let mut framebuffer = glium::framebuffer::SimpleFrameBuffer::new(display, &texture)
+        .expect("Failed to create framebuffer for material texture");
+
+    let shader = &program.get_shader("pack_material").unwrap();
+
+    let vertex_buffer = program.get_full_screen_quad();
+
+    let uniforms = &uniform! {
+        coverage: albedo_sampler,
+        reflectivity_map: reflectivity_sampler,
+        roughness_map: roughness_sampler,
+        metalness_map: metalness_sampler,
+    };
+
+    framebuffer
+        .draw(
+            vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &Default::default(),
+        )
+        .unwrap();
+
+    texture
+}
+
 // Profiling seems to indicate that the glium clear color is the slowest part of the rendering
 // process. So this this is a simpler and faster version of the clear color function
 pub(crate) fn faster_clear_color(
@@ -312,6 +832,7 @@ pub(crate) fn draw_recieve_shadows(
     framebuffer: &mut SimpleFrameBuffer,
     program: &LumenpyxProgram,
     recieve_shadows_strength: f32,
+    light_layers: u8,
     last_frame_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
     this_frame_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
 ) {
@@ -327,6 +848,7 @@ pub(crate) fn draw_recieve_shadows(
         last_draw: last_frame_sampler,
         this_draw: this_frame_sampler,
         shadow_strength: recieve_shadows_strength,
+        light_layers: light_layers as f32 / 255.0,
     };
 
     framebuffer
@@ -398,7 +920,7 @@ pub(crate) fn load_all_system_shaders(program: &mut LumenpyxProgram) {
         let reflection_shader = glium::Program::from_source(
             display,
             REFLECTION_VERTEX_SHADER_SRC,
-            REFLECTION_FRAGMENT_SHADER_SRC,
+            &preprocess_shader(REFLECTION_FRAGMENT_SHADER_SRC).unwrap(),
             None,
         )
         .expect("Failed to load reflection shader");
@@ -417,4 +939,193 @@ pub(crate) fn load_all_system_shaders(program: &mut LumenpyxProgram) {
         .unwrap();
         program.add_shader(shader, "fill_alpha");
     }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            PACK_MATERIAL_VERTEX_SHADER_SRC,
+            PACK_MATERIAL_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load pack material shader");
+        program.add_shader(shader, "pack_material");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            COPY_TEXTURE_VERTEX_SHADER_SRC,
+            COPY_TEXTURE_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load copy texture shader");
+        program.add_shader(shader, "copy_texture");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            BLOOM_BLUR_VERTEX_SHADER_SRC,
+            BLOOM_BLUR_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load bloom blur shader");
+        program.add_shader(shader, "bloom_blur_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            GLOBAL_ILLUMINATION_VERTEX_SHADER_SRC,
+            GLOBAL_ILLUMINATION_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load global illumination shader");
+        program.add_shader(shader, "global_illumination_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            AMBIENT_OCCLUSION_VERTEX_SHADER_SRC,
+            AMBIENT_OCCLUSION_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load ambient occlusion shader");
+        program.add_shader(shader, "ambient_occlusion_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            AMBIENT_LIGHT_VERTEX_SHADER_SRC,
+            AMBIENT_LIGHT_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load ambient light shader");
+        program.add_shader(shader, "ambient_light_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            LOCAL_MAX_VERTEX_SHADER_SRC,
+            LOCAL_MAX_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load local max shader");
+        program.add_shader(shader, "local_max_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            crate::lights::BATCHED_POINT_LIGHT_VERTEX_SHADER_SRC,
+            &preprocess_shader(crate::lights::BATCHED_POINT_LIGHT_FRAGMENT_SHADER_SRC).unwrap(),
+            None,
+        )
+        .expect("Failed to load batched point light shader");
+        program.add_shader(shader, "batched_point_light_shader");
+    }
+
+    {
+        let display = &program.display;
+        let shader = glium::Program::from_source(
+            display,
+            LIGHT_UPSAMPLE_VERTEX_SHADER_SRC,
+            LIGHT_UPSAMPLE_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load light upsample shader");
+        program.add_shader(shader, "light_upsample_shader");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_include(source: &str) -> bool {
+        source
+            .lines()
+            .any(|line| line.trim().starts_with("#include"))
+    }
+
+    #[test]
+    fn shaders_without_includes_are_unchanged() {
+        let source = "void main() {\n    gl_FragColor = vec4(1.0);\n}";
+
+        assert_eq!(preprocess_shader(source).unwrap(), format!("{}\n", source));
+    }
+
+    #[test]
+    fn includes_are_replaced_by_their_snippet() {
+        let processed = preprocess_shader("#include \"falloff.glsl\"").unwrap();
+
+        assert!(processed.contains(FALLOFF_GLSL_SRC.lines().last().unwrap()));
+        assert!(!has_include(&processed));
+    }
+
+    #[test]
+    fn nested_includes_are_replaced() {
+        let processed = preprocess_shader("#include \"shadows.glsl\"").unwrap();
+
+        assert_eq!(processed.matches("bool trace_heightmap(").count(), 1);
+        assert!(!has_include(&processed));
+    }
+
+    #[test]
+    fn snippets_are_only_included_once() {
+        let processed = preprocess_shader(
+            "#include \"height_trace.glsl\"\n#include \"shadows.glsl\"\n#include \"height_trace.glsl\"",
+        )
+        .unwrap();
+
+        assert_eq!(processed.matches("bool trace_heightmap(").count(), 1);
+        assert_eq!(processed.matches("bool find_intersections(").count(), 1);
+    }
+
+    #[test]
+    fn unknown_includes_are_an_error() {
+        let error = preprocess_shader("#include \"missing.glsl\"").unwrap_err();
+
+        assert_eq!(
+            error,
+            UnknownShaderInclude {
+                name: "missing.glsl".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_nested_includes_are_an_error() {
+        // a snippet can't include a missing snippet, but the error has to come through the recursion
+        let mut processed = String::new();
+        let mut included = vec![];
+
+        let error = preprocess_shader_into(
+            "#include \"falloff.glsl\"\n#include \"missing.glsl\"",
+            &mut processed,
+            &mut included,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.name, "missing.glsl");
+        assert_eq!(included, vec!["falloff.glsl"]);
+    }
+
+    #[test]
+    fn every_snippet_preprocesses() {
+        for (name, _) in GLSL_SNIPPETS {
+            assert!(preprocess_shader(&format!("#include \"{}\"", name)).is_ok());
+        }
+    }
 }
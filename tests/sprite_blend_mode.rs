@@ -0,0 +1,30 @@
+//! A sprite can only be made with a window to upload its textures to,
+//! and winit only opens one on the main thread, so this runs without the test harness.
+//! It is skipped when there is no display to open the window on
+use lumenpyx::drawable_object::{BlendMode, Drawable};
+use lumenpyx::primitives::{Normal, Sprite};
+use lumenpyx::{LumenpyxProgram, Transform};
+
+fn main() {
+    if cfg!(target_os = "linux")
+        && std::env::var_os("DISPLAY").is_none()
+        && std::env::var_os("WAYLAND_DISPLAY").is_none()
+    {
+        println!("test sprite_defaults_to_the_normal_blend_mode ... skipped, there is no display");
+        return;
+    }
+
+    let (program, _event_loop) = LumenpyxProgram::new([16, 16], "sprite blend mode");
+    let sprite = Sprite::new(
+        [1.0, 1.0, 1.0, 1.0].into(),
+        [1.0, 1.0, 1.0, 1.0].into(),
+        [0.0, 0.0, 0.0, 1.0].into(),
+        Normal::AutoGenerated,
+        [0.0, 0.0, 0.0, 0.0].into(),
+        &program,
+        Transform::new([0.0, 0.0, 0.0]),
+    );
+
+    assert_eq!(sprite.get_blend_mode(), BlendMode::Normal);
+    println!("test sprite_defaults_to_the_normal_blend_mode ... ok");
+}